@@ -0,0 +1,155 @@
+use crate::prelude::OrmSerializable;
+use sea_orm::{entity::prelude::*, Condition, QueryOrder, Select, Set};
+use serde::{Deserialize, Serialize};
+
+impl OrmSerializable for Model {}
+
+/// Failed logins for one email within `LOCKOUT_MINUTES` that lock it until they age out
+pub const MAX_FAILED_LOGINS: u64 = 5;
+/// Failed logins from one ip within `LOCKOUT_MINUTES`, across every email, before it is throttled
+pub const MAX_FAILED_LOGINS_PER_IP: u64 = 20;
+pub const LOCKOUT_MINUTES: i64 = 15;
+
+pub const LOGIN: &str = "login";
+pub const LOGOUT: &str = "logout";
+pub const FAILED_LOGIN: &str = "failed_login";
+/// a login refused because the email was locked or the ip throttled, not counted as a failure
+pub const LOCKED_OUT: &str = "locked_out";
+pub const REFRESH_REUSED: &str = "refresh_reused";
+pub const SESSIONS_REVOKED: &str = "sessions_revoked";
+pub const PASSWORD_CHANGED: &str = "password_changed";
+pub const PASSWORD_RESET: &str = "password_reset";
+pub const DISABLED: &str = "disabled";
+
+/// Login history, kept for audits and to count failed attempts towards a lockout
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "auth_events")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    /// none when the email doesn't belong to a user
+    pub user_id: Option<i32>,
+    pub email: String,
+    pub ip: Option<String>,
+    pub kind: String,
+    pub created_at: DateTime,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AuthEventFilter {
+    pub user_id: Option<i32>,
+    pub email: Option<String>,
+    pub ip: Option<String>,
+    pub kind: Option<String>,
+    pub start: Option<chrono::NaiveDate>,
+    pub end: Option<chrono::NaiveDate>,
+    pub page: Option<u64>,
+    pub per_page: Option<u64>,
+}
+
+impl AuthEventFilter {
+    /// Matching events, newest first
+    pub fn query(&self) -> Select<Entity> {
+        let mut cond = Condition::all();
+        if let Some(user_id) = self.user_id {
+            cond = cond.add(Column::UserId.eq(user_id));
+        }
+        if let Some(email) = &self.email {
+            cond = cond.add(Column::Email.eq(email.as_str()));
+        }
+        if let Some(ip) = &self.ip {
+            cond = cond.add(Column::Ip.eq(ip.as_str()));
+        }
+        if let Some(kind) = &self.kind {
+            cond = cond.add(Column::Kind.eq(kind.as_str()));
+        }
+        if let Some(start) = self.start {
+            cond = cond.add(Column::CreatedAt.gte(start.and_time(chrono::NaiveTime::MIN)));
+        }
+        if let Some(end) = self.end.and_then(|end| end.succ_opt()) {
+            cond = cond.add(Column::CreatedAt.lt(end.and_time(chrono::NaiveTime::MIN)));
+        }
+        Entity::find()
+            .filter(cond)
+            .order_by_desc(Column::CreatedAt)
+            .order_by_desc(Column::Id)
+    }
+}
+
+impl Model {
+    pub async fn record(
+        db: &DatabaseConnection,
+        kind: &str,
+        email: &str,
+        user_id: Option<i32>,
+        ip: Option<String>,
+    ) -> Result<Self, DbErr> {
+        ActiveModel {
+            user_id: Set(user_id),
+            email: Set(email.to_string()),
+            ip: Set(ip),
+            kind: Set(kind.to_string()),
+            created_at: Set(chrono::Local::now().naive_local()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    fn window_start() -> DateTime {
+        chrono::Local::now().naive_local() - chrono::Duration::minutes(LOCKOUT_MINUTES)
+    }
+
+    /// Counted by email rather than user so unknown emails lock the same way, and a lockout
+    /// doesn't give away which emails have an account. A successful login starts the count over.
+    pub async fn is_locked(db: &DatabaseConnection, email: &str) -> Result<bool, DbErr> {
+        let mut since = Self::window_start();
+        let last_login = Entity::find()
+            .filter(Column::Email.eq(email))
+            .filter(Column::Kind.eq(LOGIN))
+            .filter(Column::CreatedAt.gte(since))
+            .order_by_desc(Column::CreatedAt)
+            .one(db)
+            .await?;
+        if let Some(login) = last_login {
+            since = login.created_at;
+        }
+        let failures = Entity::find()
+            .filter(Column::Email.eq(email))
+            .filter(Column::Kind.eq(FAILED_LOGIN))
+            .filter(Column::CreatedAt.gte(since))
+            .count(db)
+            .await?;
+        Ok(failures >= MAX_FAILED_LOGINS)
+    }
+
+    pub async fn is_throttled(db: &DatabaseConnection, ip: &str) -> Result<bool, DbErr> {
+        let failures = Entity::find()
+            .filter(Column::Ip.eq(ip))
+            .filter(Column::Kind.eq(FAILED_LOGIN))
+            .filter(Column::CreatedAt.gte(Self::window_start()))
+            .count(db)
+            .await?;
+        Ok(failures >= MAX_FAILED_LOGINS_PER_IP)
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::Id",
+        on_update = "NoAction",
+        on_delete = "SetNull"
+    )]
+    Users,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
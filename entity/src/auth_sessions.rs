@@ -0,0 +1,182 @@
+use crate::prelude::OrmSerializable;
+use rand::RngCore;
+use sea_orm::{
+    entity::prelude::*, sea_query::Expr, IntoActiveModel, QueryOrder, QuerySelect, QueryTrait, Set,
+    TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+
+impl OrmSerializable for Model {}
+
+/// One login. Every token issued for it carries the `session_key`, so revoking the session
+/// refuses its cookie, access and refresh tokens alike before they expire.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "auth_sessions")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub user_id: i32,
+    #[serde(skip)]
+    pub session_key: String,
+    /// id of the only refresh token that can still be used, rotated on every refresh
+    #[serde(skip)]
+    pub refresh_key: String,
+    pub ip: Option<String>,
+    pub created_at: DateTime,
+    pub refreshed_at: Option<DateTime>,
+    pub expires_at: DateTime,
+    pub revoked_at: Option<DateTime>,
+}
+
+/// What became of a refresh token
+#[derive(Clone, Debug)]
+pub enum RefreshOutcome {
+    Refreshed(Model),
+    /// an already rotated refresh token was replayed, so the whole session was revoked
+    Reused,
+    Inactive,
+}
+
+impl Model {
+    pub fn generate_key() -> String {
+        let mut bytes = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        hex::encode(bytes)
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none() && self.expires_at > chrono::Local::now().naive_local()
+    }
+
+    pub async fn start(
+        db: &DatabaseConnection,
+        user_id: i32,
+        ip: Option<String>,
+        lifetime: chrono::Duration,
+    ) -> Result<Self, DbErr> {
+        let now = chrono::Local::now().naive_local();
+        ActiveModel {
+            user_id: Set(user_id),
+            session_key: Set(Self::generate_key()),
+            refresh_key: Set(Self::generate_key()),
+            ip: Set(ip),
+            created_at: Set(now),
+            refreshed_at: Set(None),
+            expires_at: Set(now + lifetime),
+            revoked_at: Set(None),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+
+    /// The session a token belongs to, if it hasn't been revoked or run out
+    pub async fn find_active(
+        db: &DatabaseConnection,
+        session_key: &str,
+    ) -> Result<Option<Self>, DbErr> {
+        Ok(Entity::find()
+            .filter(Column::SessionKey.eq(session_key))
+            .one(db)
+            .await?
+            .filter(Self::is_active))
+    }
+
+    /// Swaps the refresh token for a new one. Presenting one that was already swapped means it
+    /// leaked, so the session is revoked for both whoever stole it and its owner.
+    pub async fn refresh(
+        db: &DatabaseConnection,
+        session_key: &str,
+        refresh_key: &str,
+    ) -> Result<RefreshOutcome, DbErr> {
+        let txn = db.begin().await?;
+        let Some(session) = Entity::find()
+            .filter(Column::SessionKey.eq(session_key))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+            .filter(Self::is_active)
+        else {
+            return Ok(RefreshOutcome::Inactive);
+        };
+        let now = chrono::Local::now().naive_local();
+        let mut active = session.clone().into_active_model();
+        let outcome = if session.refresh_key == refresh_key {
+            active.refresh_key = Set(Self::generate_key());
+            active.refreshed_at = Set(Some(now));
+            RefreshOutcome::Refreshed(active.update(&txn).await?)
+        } else {
+            active.revoked_at = Set(Some(now));
+            active.update(&txn).await?;
+            RefreshOutcome::Reused
+        };
+        txn.commit().await?;
+        Ok(outcome)
+    }
+
+    pub async fn revoke(db: &DatabaseConnection, session_key: &str) -> Result<u64, DbErr> {
+        Entity::update_many()
+            .col_expr(
+                Column::RevokedAt,
+                Expr::value(chrono::Local::now().naive_local()),
+            )
+            .filter(Column::SessionKey.eq(session_key))
+            .filter(Column::RevokedAt.is_null())
+            .exec(db)
+            .await
+            .map(|res| res.rows_affected)
+    }
+
+    /// Logs the user out everywhere, or everywhere but the session they're using
+    pub async fn revoke_user(
+        db: &DatabaseConnection,
+        user_id: i32,
+        keep: Option<i32>,
+    ) -> Result<u64, DbErr> {
+        Entity::update_many()
+            .col_expr(
+                Column::RevokedAt,
+                Expr::value(chrono::Local::now().naive_local()),
+            )
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::RevokedAt.is_null())
+            .apply_if(keep, |query, id| query.filter(Column::Id.ne(id)))
+            .exec(db)
+            .await
+            .map(|res| res.rows_affected)
+    }
+
+    /// The user's sessions that can still be used, newest first
+    pub async fn active_for_user(
+        db: &DatabaseConnection,
+        user_id: i32,
+    ) -> Result<Vec<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::UserId.eq(user_id))
+            .filter(Column::RevokedAt.is_null())
+            .filter(Column::ExpiresAt.gt(chrono::Local::now().naive_local()))
+            .order_by_desc(Column::CreatedAt)
+            .all(db)
+            .await
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Users,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
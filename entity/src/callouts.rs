@@ -0,0 +1,266 @@
+use crate::prelude::OrmSerializable;
+use crate::{residents, timestamps};
+use sea_orm::{entity::prelude::*, sea_query::Expr, Condition, IntoActiveModel, QueryOrder, Set};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+impl OrmSerializable for Model {}
+impl OrmSerializable for OverdueCallout {}
+
+pub const PENDING: &str = "pending";
+pub const ON_TIME: &str = "on_time";
+pub const LATE: &str = "late";
+pub const MISSED: &str = "missed";
+
+/// A resident due at a location, e.g. CHAPEL at 14:00, and when they should be back
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "callouts")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub doc: i32,
+    pub location: i32,
+    /// scans before this don't count as arriving
+    pub arrive_from: DateTime,
+    /// arriving after this is late
+    pub arrive_by: DateTime,
+    /// the resident should have left the location by then, never arriving by then is missed
+    pub return_by: DateTime,
+    /// pending, on_time, late or missed
+    pub status: String,
+    pub arrived_at: Option<DateTime>,
+    pub returned_at: Option<DateTime>,
+    pub note: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PostCallout {
+    pub doc: i32,
+    pub location: i32,
+    pub arrive_from: DateTime,
+    pub arrive_by: DateTime,
+    pub return_by: DateTime,
+    pub note: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OverdueCallout {
+    pub callout: Model,
+    pub name: String,
+    /// not_arrived or not_returned
+    pub overdue: String,
+}
+
+impl PostCallout {
+    pub fn is_valid(&self) -> bool {
+        self.arrive_from <= self.arrive_by && self.arrive_by <= self.return_by
+    }
+
+    pub fn into_active_model(self) -> ActiveModel {
+        ActiveModel {
+            doc: Set(self.doc),
+            location: Set(self.location),
+            arrive_from: Set(self.arrive_from),
+            arrive_by: Set(self.arrive_by),
+            return_by: Set(self.return_by),
+            status: Set(PENDING.to_string()),
+            arrived_at: Set(None),
+            returned_at: Set(None),
+            note: Set(self.note),
+            ..Default::default()
+        }
+    }
+}
+
+impl Model {
+    /// Matches a recorded scan against the resident's callouts, as arriving at the callout location
+    /// or as leaving it after having arrived
+    pub async fn match_scan<C: ConnectionTrait>(
+        db: &C,
+        timestamp: &timestamps::Model,
+        from_location: i32,
+    ) -> Result<(), DbErr> {
+        let arriving = Entity::find()
+            .filter(Column::Doc.eq(timestamp.doc))
+            .filter(Column::Location.eq(timestamp.location))
+            .filter(Column::Status.eq(PENDING))
+            .filter(Column::ArriveFrom.lte(timestamp.ts))
+            .filter(Column::ReturnBy.gt(timestamp.ts))
+            .order_by_asc(Column::ArriveBy)
+            .one(db)
+            .await?;
+        if let Some(callout) = arriving {
+            let status = if timestamp.ts <= callout.arrive_by {
+                ON_TIME
+            } else {
+                LATE
+            };
+            let mut callout = callout.into_active_model();
+            callout.status = Set(status.to_string());
+            callout.arrived_at = Set(Some(timestamp.ts));
+            callout.update(db).await?;
+        }
+        if from_location != 0 && from_location != timestamp.location {
+            Entity::update_many()
+                .col_expr(Column::ReturnedAt, Expr::value(timestamp.ts))
+                .filter(Column::Doc.eq(timestamp.doc))
+                .filter(Column::Location.eq(from_location))
+                .filter(Column::ArrivedAt.lte(timestamp.ts))
+                .filter(Column::ReturnedAt.is_null())
+                .exec(db)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Matches the resident's callouts again from the timestamps that still count, after a supervisor
+    /// inserted, moved or voided one at `since` or later
+    pub async fn rematch<C: ConnectionTrait>(
+        db: &C,
+        doc: i32,
+        since: DateTime,
+    ) -> Result<(), DbErr> {
+        // a scan from then on can only have arrived at one still open, or left one not returned from before
+        let affected = Entity::find()
+            .filter(Column::Doc.eq(doc))
+            .filter(
+                Condition::any()
+                    .add(Column::ReturnBy.gt(since))
+                    .add(Column::ReturnedAt.gte(since))
+                    .add(
+                        Condition::all()
+                            .add(Column::ArrivedAt.is_not_null())
+                            .add(Column::ReturnedAt.is_null()),
+                    ),
+            )
+            .all(db)
+            .await?;
+        let Some(from) = affected.iter().map(|callout| callout.arrive_from).min() else {
+            return Ok(());
+        };
+        Entity::update_many()
+            .col_expr(Column::Status, Expr::value(PENDING))
+            .col_expr(Column::ArrivedAt, Expr::value(Option::<DateTime>::None))
+            .col_expr(Column::ReturnedAt, Expr::value(Option::<DateTime>::None))
+            .filter(Column::Id.is_in(affected.iter().map(|callout| callout.id)))
+            .exec(db)
+            .await?;
+        let mut from_location = timestamps::Entity::find()
+            .filter(timestamps::Column::Doc.eq(doc))
+            .filter(timestamps::Column::IsVoided.eq(false))
+            .filter(timestamps::Column::Ts.lt(from))
+            .order_by_desc(timestamps::Column::Ts)
+            .one(db)
+            .await?
+            .map_or(0, |prior| prior.location);
+        let replayed = timestamps::Entity::find()
+            .filter(timestamps::Column::Doc.eq(doc))
+            .filter(timestamps::Column::IsVoided.eq(false))
+            .filter(timestamps::Column::Ts.gte(from))
+            .order_by_asc(timestamps::Column::Ts)
+            .all(db)
+            .await?;
+        for timestamp in replayed {
+            Self::match_scan(db, &timestamp, from_location).await?;
+            from_location = timestamp.location;
+        }
+        Ok(())
+    }
+
+    /// A pending callout nobody showed up to before its return time reads as missed
+    pub fn with_status_at(mut self, now: DateTime) -> Self {
+        if self.status == PENDING && self.return_by < now {
+            self.status = MISSED.to_string();
+        }
+        self
+    }
+
+    /// Residents late to arrive at the location, or still there past their return time
+    pub async fn overdue_at(
+        db: &DatabaseConnection,
+        location: i32,
+    ) -> Result<Vec<OverdueCallout>, DbErr> {
+        let now = chrono::Local::now().naive_local();
+        let callouts = Entity::find()
+            .filter(Column::Location.eq(location))
+            .filter(
+                Condition::any()
+                    .add(
+                        Condition::all()
+                            .add(Column::Status.eq(PENDING))
+                            .add(Column::ArriveBy.lt(now))
+                            .add(Column::ReturnBy.gte(now)),
+                    )
+                    .add(
+                        Condition::all()
+                            .add(Column::ArrivedAt.is_not_null())
+                            .add(Column::ReturnedAt.is_null())
+                            .add(Column::ReturnBy.lt(now)),
+                    ),
+            )
+            .order_by_asc(Column::ArriveBy)
+            .all(db)
+            .await?;
+        let names: HashMap<i32, String> = residents::Entity::find()
+            .filter(
+                residents::Column::Doc.is_in(
+                    callouts
+                        .iter()
+                        .map(|callout| callout.doc)
+                        .collect::<Vec<_>>(),
+                ),
+            )
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|resident| (resident.doc, resident.name))
+            .collect();
+        Ok(callouts
+            .into_iter()
+            .map(|callout| OverdueCallout {
+                name: names.get(&callout.doc).cloned().unwrap_or_default(),
+                overdue: if callout.arrived_at.is_none() {
+                    "not_arrived"
+                } else {
+                    "not_returned"
+                }
+                .to_string(),
+                callout,
+            })
+            .collect())
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::residents::Entity",
+        from = "Column::Doc",
+        to = "super::residents::Column::Doc",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Residents,
+    #[sea_orm(
+        belongs_to = "super::locations::Entity",
+        from = "Column::Location",
+        to = "super::locations::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Locations,
+}
+
+impl Related<super::residents::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Residents.def()
+    }
+}
+
+impl Related<super::locations::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Locations.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,101 @@
+use crate::prelude::OrmSerializable;
+use rand::RngCore;
+use sea_orm::{entity::prelude::*, Set};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+impl OrmSerializable for Model {}
+impl OrmSerializable for RegisteredDevice {}
+
+/// A scanner (e.g. the Raspberry Pi RFID client) permanently bound to one location
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "devices")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub name: String,
+    pub location: i32,
+    #[serde(skip)]
+    pub api_key: String,
+    pub is_revoked: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PostDevice {
+    pub name: String,
+    pub location: i32,
+}
+
+/// Returned only once on registration, the plain api key is never stored
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RegisteredDevice {
+    pub id: i32,
+    pub name: String,
+    pub location: i32,
+    pub api_key: String,
+}
+
+impl PostDevice {
+    /// Returns the new device along with the plain api key to hand to it
+    pub fn into_active_model(self) -> (ActiveModel, String) {
+        let api_key = Model::generate_key();
+        let device = ActiveModel {
+            name: Set(self.name),
+            location: Set(self.location),
+            api_key: Set(Model::hash_key(&api_key)),
+            is_revoked: Set(false),
+            ..Default::default()
+        };
+        (device, api_key)
+    }
+}
+
+impl Model {
+    pub fn generate_key() -> String {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        hex::encode(bytes)
+    }
+    pub fn hash_key(api_key: &str) -> String {
+        hex::encode(Sha256::digest(api_key.as_bytes()))
+    }
+    /// Looks up the active device holding the given api key
+    pub async fn find_by_key(
+        db: &DatabaseConnection,
+        api_key: &str,
+    ) -> Result<Option<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::ApiKey.eq(Self::hash_key(api_key)))
+            .filter(Column::IsRevoked.eq(false))
+            .one(db)
+            .await
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::locations::Entity",
+        from = "Column::Location",
+        to = "super::locations::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    Locations,
+    #[sea_orm(has_many = "super::timestamps::Entity")]
+    Timestamps,
+}
+
+impl Related<super::locations::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Locations.def()
+    }
+}
+
+impl Related<super::timestamps::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Timestamps.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
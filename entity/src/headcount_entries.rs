@@ -0,0 +1,45 @@
+use crate::prelude::OrmSerializable;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+impl OrmSerializable for Model {}
+
+pub const EXPECTED: &str = "expected";
+pub const SIGNED_OUT: &str = "signed_out";
+/// assigned to the counted unit but scanned in somewhere else when the count started
+pub const OUT: &str = "out";
+pub const UNEXPECTED: &str = "unexpected";
+
+/// One resident in a headcount, from the snapshot taken when it started or scanned in later
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "headcount_entries")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub headcount_id: i32,
+    pub doc: i32,
+    /// expected, signed_out, out or unexpected
+    pub kind: String,
+    pub location: i32,
+    pub confirmed_at: Option<DateTime>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::headcounts::Entity",
+        from = "Column::HeadcountId",
+        to = "super::headcounts::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Headcounts,
+}
+
+impl Related<super::headcounts::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Headcounts.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,245 @@
+use crate::prelude::OrmSerializable;
+use crate::{headcount_entries, residents};
+use sea_orm::{entity::prelude::*, Condition, IntoActiveModel, Set, TransactionTrait};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+impl OrmSerializable for Model {}
+impl OrmSerializable for HeadcountReport {}
+
+/// A formal count of the residents at one location, or the whole facility when `location` is None
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "headcounts")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub location: Option<i32>,
+    pub started_by: i32,
+    pub started_at: DateTime,
+    /// scans are only accepted until the count is closed
+    pub closed_at: Option<DateTime>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PostHeadcount {
+    pub location: Option<i32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CountScan {
+    pub rfid: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HeadcountResident {
+    pub doc: i32,
+    pub name: String,
+    /// where the resident was when the count started, or when they were scanned if they weren't expected
+    pub location: i32,
+    pub confirmed_at: Option<DateTime>,
+}
+
+/// Where a count stands, everything but `missing` and `unexpected` having been accounted for
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HeadcountReport {
+    pub headcount: Model,
+    pub expected: usize,
+    pub confirmed: usize,
+    /// expected at the location but not scanned yet
+    pub missing: Vec<HeadcountResident>,
+    /// scanned during the count without being expected
+    pub unexpected: Vec<HeadcountResident>,
+    /// signed out (location 0) when the count started, so not expected anywhere
+    pub signed_out: Vec<HeadcountResident>,
+    /// assigned to the counted unit but at the `location` they were scanned into when the count started
+    pub out: Vec<HeadcountResident>,
+}
+
+#[derive(Clone, Debug)]
+pub enum CountScanOutcome {
+    Confirmed(headcount_entries::Model),
+    AlreadyConfirmed(headcount_entries::Model),
+    Closed,
+    NotFound,
+}
+
+impl PostHeadcount {
+    /// Starts the count, snapshotting who is expected from the residents' current location.
+    /// Residents of a counted unit who are signed out or somewhere else are kept apart with where they are,
+    /// facility-wide only those signed out are.
+    pub async fn start(&self, db: &DatabaseConnection, user_id: i32) -> Result<Model, DbErr> {
+        let txn = db.begin().await?;
+        let mut query = residents::Entity::find().filter(residents::Column::IsDeleted.eq(false));
+        if let Some(location) = self.location {
+            query = query.filter(
+                Condition::any()
+                    .add(residents::Column::CurrentLocation.eq(location))
+                    .add(residents::Column::Unit.eq(location)),
+            );
+        }
+        let residents = query.all(&txn).await?;
+        let headcount = ActiveModel {
+            location: Set(self.location),
+            started_by: Set(user_id),
+            started_at: Set(chrono::Local::now().naive_local()),
+            closed_at: Set(None),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        let entries: Vec<headcount_entries::ActiveModel> = residents
+            .iter()
+            .map(|resident| headcount_entries::ActiveModel {
+                headcount_id: Set(headcount.id),
+                doc: Set(resident.doc),
+                kind: Set(match resident.current_location {
+                    0 => headcount_entries::SIGNED_OUT,
+                    at if self.location.is_some_and(|location| location != at) => {
+                        headcount_entries::OUT
+                    }
+                    _ => headcount_entries::EXPECTED,
+                }
+                .to_string()),
+                location: Set(resident.current_location),
+                confirmed_at: Set(None),
+                ..Default::default()
+            })
+            .collect();
+        if !entries.is_empty() {
+            headcount_entries::Entity::insert_many(entries)
+                .exec(&txn)
+                .await?;
+        }
+        txn.commit().await?;
+        Ok(headcount)
+    }
+}
+
+impl Model {
+    pub fn is_open(&self) -> bool {
+        self.closed_at.is_none()
+    }
+
+    /// Marks the scanned resident as seen, adding them as unexpected if they weren't in the snapshot
+    pub async fn confirm(
+        &self,
+        db: &DatabaseConnection,
+        rfid: &str,
+    ) -> Result<CountScanOutcome, DbErr> {
+        if !self.is_open() {
+            return Ok(CountScanOutcome::Closed);
+        }
+        let Some(resident) = residents::Entity::find()
+            .filter(residents::Column::Rfid.eq(rfid))
+            .filter(residents::Column::IsDeleted.eq(false))
+            .one(db)
+            .await?
+        else {
+            return Ok(CountScanOutcome::NotFound);
+        };
+        let now = chrono::Local::now().naive_local();
+        let entry = headcount_entries::Entity::find()
+            .filter(headcount_entries::Column::HeadcountId.eq(self.id))
+            .filter(headcount_entries::Column::Doc.eq(resident.doc))
+            .one(db)
+            .await?;
+        match entry {
+            Some(entry) if entry.confirmed_at.is_some() => {
+                Ok(CountScanOutcome::AlreadyConfirmed(entry))
+            }
+            Some(entry) => {
+                let mut entry = entry.into_active_model();
+                entry.confirmed_at = Set(Some(now));
+                Ok(CountScanOutcome::Confirmed(entry.update(db).await?))
+            }
+            None => {
+                let entry = headcount_entries::ActiveModel {
+                    headcount_id: Set(self.id),
+                    doc: Set(resident.doc),
+                    kind: Set(headcount_entries::UNEXPECTED.to_string()),
+                    location: Set(resident.current_location),
+                    confirmed_at: Set(Some(now)),
+                    ..Default::default()
+                };
+                Ok(CountScanOutcome::Confirmed(entry.insert(db).await?))
+            }
+        }
+    }
+
+    pub async fn close(self, db: &DatabaseConnection) -> Result<Self, DbErr> {
+        let mut headcount = self.into_active_model();
+        headcount.closed_at = Set(Some(chrono::Local::now().naive_local()));
+        headcount.update(db).await
+    }
+
+    pub async fn report(self, db: &DatabaseConnection) -> Result<HeadcountReport, DbErr> {
+        let entries = headcount_entries::Entity::find()
+            .filter(headcount_entries::Column::HeadcountId.eq(self.id))
+            .all(db)
+            .await?;
+        let names: HashMap<i32, String> = residents::Entity::find()
+            .filter(
+                residents::Column::Doc
+                    .is_in(entries.iter().map(|entry| entry.doc).collect::<Vec<_>>()),
+            )
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|resident| (resident.doc, resident.name))
+            .collect();
+        let resident = |entry: &headcount_entries::Model| HeadcountResident {
+            doc: entry.doc,
+            name: names.get(&entry.doc).cloned().unwrap_or_default(),
+            location: entry.location,
+            confirmed_at: entry.confirmed_at,
+        };
+        let of_kind = |kind: &'static str| entries.iter().filter(move |entry| entry.kind == kind);
+        Ok(HeadcountReport {
+            expected: of_kind(headcount_entries::EXPECTED).count(),
+            confirmed: entries
+                .iter()
+                .filter(|entry| entry.confirmed_at.is_some())
+                .count(),
+            missing: of_kind(headcount_entries::EXPECTED)
+                .filter(|entry| entry.confirmed_at.is_none())
+                .map(resident)
+                .collect(),
+            unexpected: of_kind(headcount_entries::UNEXPECTED)
+                .map(resident)
+                .collect(),
+            signed_out: of_kind(headcount_entries::SIGNED_OUT)
+                .map(resident)
+                .collect(),
+            out: of_kind(headcount_entries::OUT).map(resident).collect(),
+            headcount: self,
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::headcount_entries::Entity")]
+    Entries,
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::StartedBy",
+        to = "super::users::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    Users,
+}
+
+impl Related<super::headcount_entries::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Entries.def()
+    }
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
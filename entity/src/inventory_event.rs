@@ -1,19 +1,114 @@
 use crate::prelude::OrmSerializable;
-use sea_orm::entity::prelude::*;
+use sea_orm::{entity::prelude::*, QueryOrder, QuerySelect};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq, DeriveEntityModel)]
 #[sea_orm(table_name = "inventory_events")]
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = true)]
     pub id: i32,
-    pub purchase_order_id: i32,
+    /// the order the stock was received against, none for sales and manual adjustments
+    pub purchase_order_id: Option<i32>,
     pub item_id: i32,
     pub quantity: i32,
     pub is_add: bool,
+    /// cents a unit the stock moved at, what it was bought for when received and the average cost otherwise
+    pub unit_cost: i32,
+    pub created_at: DateTime,
+    /// why stock was adjusted by hand or by a count, none for receipts, sales and refunds
+    pub reason: Option<String>,
 }
 
+/// Reasons stock can be adjusted for
+pub const REASONS: [&str; 6] = [
+    "adjustment",
+    "cycle_count",
+    "damaged",
+    "expired",
+    "theft",
+    "miscount",
+];
+
 impl OrmSerializable for Model {}
+impl OrmSerializable for InventoryValuation {}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ItemValuation {
+    pub item_id: i32,
+    pub upc: String,
+    pub name: String,
+    pub quantity: i64,
+    /// average cost in cents of a unit on hand
+    pub unit_cost: i64,
+    /// cents
+    pub value: i64,
+}
+
+/// What the stock on hand was worth at `as_of`, amounts in cents
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InventoryValuation {
+    pub as_of: DateTime,
+    pub items: Vec<ItemValuation>,
+    pub total_value: i64,
+}
+
+/// Adds up every item's inventory events up to `as_of`, each moving stock in or out at its unit cost
+pub async fn valuation(
+    db: &DatabaseConnection,
+    as_of: DateTime,
+) -> Result<InventoryValuation, DbErr> {
+    let sums: Vec<(i32, Option<i64>, Option<i64>)> = Entity::find()
+        .select_only()
+        .column(Column::ItemId)
+        .column_as(
+            Expr::cust("SUM(CASE WHEN is_add THEN quantity ELSE -quantity END)"),
+            "quantity",
+        )
+        .column_as(
+            Expr::cust(
+                "SUM(CASE WHEN is_add THEN 1 ELSE -1 END * quantity::bigint * unit_cost)::bigint",
+            ),
+            "value",
+        )
+        .filter(Column::CreatedAt.lte(as_of))
+        .group_by(Column::ItemId)
+        .into_tuple()
+        .all(db)
+        .await?;
+    let items: HashMap<i32, super::items::Model> = super::items::Entity::find()
+        .filter(super::items::Column::Id.is_in(sums.iter().map(|(item_id, _, _)| *item_id)))
+        .order_by_asc(super::items::Column::Id)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|item| (item.id, item))
+        .collect();
+    let mut valued: Vec<ItemValuation> = sums
+        .into_iter()
+        .filter_map(|(item_id, quantity, value)| {
+            let (quantity, value) = (quantity.unwrap_or(0), value.unwrap_or(0));
+            if quantity == 0 && value == 0 {
+                return None;
+            }
+            let item = items.get(&item_id).cloned().unwrap_or_default();
+            Some(ItemValuation {
+                item_id,
+                upc: item.upc,
+                name: item.name,
+                quantity,
+                unit_cost: if quantity > 0 { value / quantity } else { 0 },
+                value,
+            })
+        })
+        .collect();
+    valued.sort_by_key(|item| item.item_id);
+    Ok(InventoryValuation {
+        as_of,
+        total_value: valued.iter().map(|item| item.value).sum(),
+        items: valued,
+    })
+}
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
@@ -23,6 +118,18 @@ pub enum Relation {
         to = "super::items::Column::Id"
     )]
     Items,
+    #[sea_orm(
+        belongs_to = "super::purchase_orders::Entity",
+        from = "Column::PurchaseOrderId",
+        to = "super::purchase_orders::Column::Id"
+    )]
+    PurchaseOrders,
+}
+
+impl Related<super::purchase_orders::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PurchaseOrders.def()
+    }
 }
 
 impl Related<super::items::Entity> for Entity {
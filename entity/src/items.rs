@@ -1,7 +1,11 @@
 use std::ops::Mul;
 
-use sea_orm::{entity::prelude::*, IntoActiveModel, Set};
+use sea_orm::{
+    entity::prelude::*, sea_query::Expr, IntoActiveModel, QueryOrder, QuerySelect, Set,
+    TransactionTrait,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 use crate::prelude::OrmSerializable;
 
@@ -14,40 +18,75 @@ pub struct Model {
     pub name: String,
     pub price: i32,
     pub quantity: i32,
+    /// weighted average cost in cents of the stock on hand
+    #[serde(default)]
+    pub unit_cost: i32,
+    /// stock at or below this is low and listed for reordering
+    pub reorder_point: Option<i32>,
+    /// how many to reorder at a time
+    pub reorder_quantity: Option<i32>,
     #[serde(skip)]
     pub is_deleted: bool,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct PatchItem {
-    pub purchase_order_id: i32,
     pub price: Option<f64>,
+    /// added to (or taken from, when negative) the stock as a manual adjustment,
+    /// stock that arrives from a vendor is received against its purchase order
     pub quantity: Option<i32>,
+    /// one of the inventory event reasons, `adjustment` when not given
+    pub reason: Option<String>,
+    pub reorder_point: Option<i32>,
+    pub reorder_quantity: Option<i32>,
 }
 
 impl PatchItem {
+    pub fn is_valid(&self) -> bool {
+        self.reason
+            .as_deref()
+            .is_none_or(|reason| crate::inventory_event::REASONS.contains(&reason))
+            && self.reorder_point.is_none_or(|point| point >= 0)
+            && self.reorder_quantity.is_none_or(|quantity| quantity > 0)
+    }
+
+    /// The item stays locked until the adjustment and its inventory event are both written,
+    /// so a sale at the same time waits instead of being overwritten
     #[rustfmt::skip]
     pub async fn update_item(&self, id: i32, db: &DatabaseConnection) -> Result<ActiveModel, sea_orm::DbErr> {
+        let txn = db.begin().await?;
         let model = Entity::find_by_id(id)
-            .one(db)
+            .lock_exclusive()
+            .one(&txn)
             .await?
-            .expect("Item not found");
-        let mut active = model.into_active_model();
+            .ok_or(DbErr::RecordNotFound("Item not found".to_string()))?;
+        let mut active = model.clone().into_active_model();
         if let Some(price) = self.price {
-            active.price = Set(price.mul(100.0) as i32);
+            active.price = Set(price.mul(100.0).round() as i32);
+        }
+        if let Some(point) = self.reorder_point {
+            active.reorder_point = Set(Some(point));
+        }
+        if let Some(quantity) = self.reorder_quantity {
+            active.reorder_quantity = Set(Some(quantity));
         }
-        if let Some(quantity) = self.quantity {
+        if let Some(quantity) = self.quantity.filter(|quantity| *quantity != 0) {
             active.quantity = Set(active.quantity.to_owned().unwrap() + quantity);
+            let event = crate::inventory_event::ActiveModel {
+                item_id: Set(id),
+                quantity: Set(quantity.abs()),
+                purchase_order_id: Set(None),
+                is_add: Set(quantity > 0),
+                unit_cost: Set(model.unit_cost),
+                created_at: Set(chrono::Local::now().naive_local()),
+                reason: Set(Some(self.reason.clone().unwrap_or_else(|| "adjustment".to_string()))),
+                ..Default::default()
+            };
+            event.save(&txn).await?;
         }
-        let order = crate::inventory_event::ActiveModel {
-            item_id: Set(id),
-            quantity: Set(self.quantity.unwrap()),
-            purchase_order_id: Set(self.purchase_order_id),
-            is_add: Set(true),
-            ..Default::default()
-        };
-        order.save(db).await?;
-        active.save(db).await
+        let active = active.save(&txn).await?;
+        txn.commit().await?;
+        Ok(active)
     }
 }
 
@@ -76,6 +115,10 @@ pub struct CreateItem {
     pub name: String,
     pub price: f64,
     pub quantity: i32,
+    /// what the opening stock cost a unit
+    pub unit_cost: Option<f64>,
+    pub reorder_point: Option<i32>,
+    pub reorder_quantity: Option<i32>,
 }
 
 impl CreateItem {
@@ -85,9 +128,125 @@ impl CreateItem {
             name: Set(self.name),
             price: Set(self.price.mul(100.0) as i32),
             quantity: Set(self.quantity),
+            unit_cost: Set(self.unit_cost.unwrap_or(0.0).mul(100.0).round() as i32),
+            reorder_point: Set(self.reorder_point),
+            reorder_quantity: Set(self.reorder_quantity),
             ..Default::default()
         }
     }
+
+    /// Stores the item, with an inventory event for any opening stock so it's valued from the start
+    pub async fn create(self, db: &DatabaseConnection) -> Result<Model, DbErr> {
+        let txn = db.begin().await?;
+        let item = self.into_active_model().insert(&txn).await?;
+        if item.quantity > 0 {
+            crate::inventory_event::ActiveModel {
+                item_id: Set(item.id),
+                quantity: Set(item.quantity),
+                is_add: Set(true),
+                purchase_order_id: Set(None),
+                unit_cost: Set(item.unit_cost),
+                created_at: Set(chrono::Local::now().naive_local()),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+        }
+        txn.commit().await?;
+        Ok(item)
+    }
+}
+
+impl OrmSerializable for LowStockItem {}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LowStockItem {
+    #[serde(flatten)]
+    pub item: Model,
+    /// still to come in on purchase orders that haven't been fully received, drafts included
+    pub on_order: i64,
+    /// units sold a day on average, less what was given back
+    pub average_daily_sales: f64,
+    /// days the stock on hand lasts at that rate, none when it isn't selling
+    pub days_until_stockout: Option<f64>,
+    /// how many to order, the reorder quantity or enough to get back to twice the reorder point
+    pub suggested_quantity: i64,
+}
+
+/// Items at or below their reorder point, with how fast they sold over the last `days` days
+pub async fn low_stock(db: &DatabaseConnection, days: i64) -> Result<Vec<LowStockItem>, DbErr> {
+    let items = Entity::find()
+        .filter(Column::IsDeleted.eq(false))
+        .filter(Column::ReorderPoint.is_not_null())
+        .filter(Expr::col(Column::Quantity).lte(Expr::col(Column::ReorderPoint)))
+        .order_by_asc(Column::Id)
+        .all(db)
+        .await?;
+    let today = chrono::Local::now().date_naive();
+    let sales = crate::transaction_items::gross_margin(
+        db,
+        today - chrono::Duration::days(days - 1),
+        today,
+        "day",
+    )
+    .await?;
+    let sold: HashMap<i32, i64> = sales
+        .items
+        .iter()
+        .map(|item| (item.item_id, item.margin.quantity))
+        .collect();
+    let on_order = crate::purchase_order_lines::outstanding(db).await?;
+    Ok(items
+        .into_iter()
+        .map(|item| {
+            let reorder_point = item.reorder_point.unwrap_or(0) as i64;
+            let on_order = on_order.get(&item.id).copied().unwrap_or(0);
+            let average_daily_sales =
+                sold.get(&item.id).copied().unwrap_or(0).max(0) as f64 / days as f64;
+            let position = item.quantity as i64 + on_order;
+            let suggested_quantity = if position > reorder_point {
+                0
+            } else {
+                item.reorder_quantity
+                    .map_or(2 * reorder_point - position, |quantity| quantity as i64)
+                    .max(1)
+            };
+            LowStockItem {
+                on_order,
+                days_until_stockout: (average_daily_sales > 0.0).then(|| {
+                    (item.quantity.max(0) as f64 / average_daily_sales * 10.0).round() / 10.0
+                }),
+                average_daily_sales: (average_daily_sales * 100.0).round() / 100.0,
+                suggested_quantity,
+                item,
+            }
+        })
+        .collect())
+}
+
+/// Adds stock that cost `unit_cost` cents a unit, averaging it into the cost of what's already on hand
+pub async fn restock<C: ConnectionTrait>(
+    db: &C,
+    id: i32,
+    quantity: i32,
+    unit_cost: i32,
+) -> Result<(), DbErr> {
+    // every SET reads the row as it was, so the average is taken over the stock before this
+    Entity::update_many()
+        .col_expr(
+            Column::UnitCost,
+            Expr::cust_with_values(
+                "CASE WHEN GREATEST(quantity, 0) + $1 > 0 \
+                 THEN ROUND((GREATEST(quantity, 0)::numeric * unit_cost + $1::numeric * $2) / (GREATEST(quantity, 0) + $1))::integer \
+                 ELSE unit_cost END",
+                [quantity, unit_cost],
+            ),
+        )
+        .col_expr(Column::Quantity, Expr::col(Column::Quantity).add(quantity))
+        .filter(Column::Id.eq(id))
+        .exec(db)
+        .await?;
+    Ok(())
 }
 
 impl Related<super::inventory_event::Entity> for Entity {
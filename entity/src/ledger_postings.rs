@@ -0,0 +1,190 @@
+use crate::prelude::OrmSerializable;
+use sea_orm::{entity::prelude::*, sea_query::Func, QueryOrder, QuerySelect, Set};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+impl OrmSerializable for Model {}
+impl OrmSerializable for Reconciliation {}
+
+/// A resident's account, the only ledger with an `account_id`
+pub const RESIDENT: &str = "resident";
+/// Money deposited into resident accounts
+pub const DEPOSITS: &str = "deposits";
+/// Commissary sales, and refunds of them
+pub const SALES: &str = "sales";
+pub const FEES: &str = "fees";
+/// Balances the accounts had before the ledger was introduced
+pub const OPENING: &str = "opening";
+/// Overdrawn balances written off when balances stopped being allowed to go negative
+pub const OVERDRAFTS: &str = "overdrafts";
+
+/// One side of a double-entry posting. Every transaction's postings sum to zero, a positive amount
+/// on the resident ledger adds to that resident's balance.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "ledger_postings")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub transaction_id: Option<i32>,
+    pub account_id: Option<i32>,
+    pub ledger: String,
+    pub amount: i32,
+    pub created_at: DateTime,
+}
+
+/// A posting before it's written, `account_id` is only set on the resident ledger
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub account_id: Option<i32>,
+    pub ledger: &'static str,
+    pub amount: i32,
+}
+
+impl Entry {
+    pub fn resident(account_id: i32, amount: i32) -> Self {
+        Self {
+            account_id: Some(account_id),
+            ledger: RESIDENT,
+            amount,
+        }
+    }
+
+    pub fn house(ledger: &'static str, amount: i32) -> Self {
+        Self {
+            account_id: None,
+            ledger,
+            amount,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AccountDiscrepancy {
+    pub account_id: i32,
+    pub doc: i32,
+    pub stored_balance: i32,
+    pub ledger_balance: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Reconciliation {
+    /// accounts whose stored balance disagrees with their postings
+    pub accounts: Vec<AccountDiscrepancy>,
+    /// transactions whose postings don't sum to zero
+    pub unbalanced_transactions: Vec<i32>,
+}
+
+/// Writes the postings for a transaction, refusing any that don't balance
+pub async fn post<C: ConnectionTrait>(
+    db: &C,
+    transaction_id: Option<i32>,
+    entries: &[Entry],
+) -> Result<(), DbErr> {
+    if entries.iter().map(|entry| entry.amount as i64).sum::<i64>() != 0 {
+        return Err(DbErr::Custom(
+            "Ledger postings must sum to zero".to_string(),
+        ));
+    }
+    let now = chrono::Local::now().naive_local();
+    Entity::insert_many(entries.iter().map(|entry| ActiveModel {
+        transaction_id: Set(transaction_id),
+        account_id: Set(entry.account_id),
+        ledger: Set(entry.ledger.to_string()),
+        amount: Set(entry.amount),
+        created_at: Set(now),
+        ..Default::default()
+    }))
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+impl Model {
+    pub async fn for_account(db: &DatabaseConnection, account_id: i32) -> Result<Vec<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::AccountId.eq(account_id))
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await
+    }
+
+    /// Every resident account's balance as its postings add up
+    pub async fn balances(db: &DatabaseConnection) -> Result<HashMap<i32, i64>, DbErr> {
+        let sums: Vec<(Option<i32>, Option<i64>)> = Entity::find()
+            .select_only()
+            .column(Column::AccountId)
+            .column_as(Expr::expr(Func::sum(Expr::col(Column::Amount))), "balance")
+            .filter(Column::Ledger.eq(RESIDENT))
+            .group_by(Column::AccountId)
+            .into_tuple()
+            .all(db)
+            .await?;
+        Ok(sums
+            .into_iter()
+            .filter_map(|(account_id, balance)| Some((account_id?, balance.unwrap_or(0))))
+            .collect())
+    }
+}
+
+/// Compares every account's stored balance against its postings, and checks each transaction balances
+pub async fn reconcile(db: &DatabaseConnection) -> Result<Reconciliation, DbErr> {
+    let balances = Model::balances(db).await?;
+    let accounts = crate::accounts::Entity::find()
+        .order_by_asc(crate::accounts::Column::Id)
+        .all(db)
+        .await?
+        .into_iter()
+        .filter_map(|account| {
+            let ledger_balance = balances.get(&account.id).copied().unwrap_or(0);
+            (ledger_balance != account.balance as i64).then_some(AccountDiscrepancy {
+                account_id: account.id,
+                doc: account.doc,
+                stored_balance: account.balance,
+                ledger_balance,
+            })
+        })
+        .collect();
+    let unbalanced_transactions: Vec<Option<i32>> = Entity::find()
+        .select_only()
+        .column(Column::TransactionId)
+        .filter(Column::TransactionId.is_not_null())
+        .group_by(Column::TransactionId)
+        .having(Expr::expr(Func::sum(Expr::col(Column::Amount))).ne(0))
+        .into_tuple()
+        .all(db)
+        .await?;
+    Ok(Reconciliation {
+        accounts,
+        unbalanced_transactions: unbalanced_transactions.into_iter().flatten().collect(),
+    })
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::accounts::Entity",
+        from = "Column::AccountId",
+        to = "super::accounts::Column::Id"
+    )]
+    Accounts,
+    #[sea_orm(
+        belongs_to = "super::transactions::Entity",
+        from = "Column::TransactionId",
+        to = "super::transactions::Column::Id"
+    )]
+    Transactions,
+}
+
+impl Related<super::accounts::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Accounts.def()
+    }
+}
+
+impl Related<super::transactions::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Transactions.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,60 @@
+use crate::prelude::OrmSerializable;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+impl OrmSerializable for Model {}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
+#[sea_orm(table_name = "levels")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub permission: i32,
+    pub text: String,
+}
+
+/// Permission rank stored in `levels.permission`, a higher rank includes every lower one
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Permission {
+    Officer = 1,
+    Clerk = 2,
+    Supervisor = 3,
+    Admin = 4,
+}
+
+impl Permission {
+    pub const ALL: [Self; 4] = [Self::Officer, Self::Clerk, Self::Supervisor, Self::Admin];
+
+    pub fn text(&self) -> &'static str {
+        match self {
+            Self::Officer => "officer",
+            Self::Clerk => "clerk",
+            Self::Supervisor => "supervisor",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+impl From<Permission> for i32 {
+    fn from(permission: Permission) -> Self {
+        permission as i32
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        has_many = "super::users::Entity",
+        from = "Column::Id",
+        to = "super::users::Column::Level"
+    )]
+    Users,
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -1,9 +1,26 @@
 pub mod accounts;
+pub mod auth_events;
+pub mod auth_sessions;
+pub mod callouts;
+pub mod devices;
+pub mod headcount_entries;
+pub mod headcounts;
 pub mod inventory_event;
 pub mod items;
+pub mod ledger_postings;
+pub mod levels;
 pub mod locations;
+pub mod movement_rules;
 pub mod prelude;
+pub mod purchase_limits;
+pub mod purchase_order_lines;
+pub mod purchase_orders;
+pub mod rejected_scans;
+pub mod resident_restrictions;
 pub mod residents;
+pub mod stock_count_entries;
+pub mod stock_counts;
+pub mod timestamp_adjustments;
 pub mod timestamps;
 pub mod transaction_items;
 pub mod transactions;
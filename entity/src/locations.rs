@@ -1,6 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.6
 use crate::prelude::OrmSerializable;
-use sea_orm::entity::prelude::*;
+use sea_orm::{entity::prelude::*, QueryOrder, QuerySelect, Set};
 use serde::{Deserialize, Serialize};
 
 impl OrmSerializable for Model {}
@@ -12,6 +12,148 @@ pub struct Model {
     pub id: i32,
     pub name: String,
     pub level: i32,
+    /// residents at a work location are on the clock for payroll
+    #[serde(default)]
+    pub is_work: bool,
+    /// most residents allowed at once, unlimited when None
+    #[serde(default)]
+    pub capacity: Option<i32>,
+    /// hours of operation, open around the clock unless both are set.
+    /// Closing before opening means the location is open past midnight.
+    #[serde(default)]
+    pub opens_at: Option<Time>,
+    #[serde(default)]
+    pub closes_at: Option<Time>,
+    /// scans into a full or closed location are rejected instead of recorded with a warning
+    #[serde(default)]
+    pub rejects_over_limit: bool,
+}
+
+/// Changes to a location, anything left out is kept as it was and `null` clears the capacity or hours
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PatchLocation {
+    pub name: Option<String>,
+    pub level: Option<i32>,
+    pub is_work: Option<bool>,
+    #[serde(default, deserialize_with = "present")]
+    pub capacity: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub opens_at: Option<Option<Time>>,
+    #[serde(default, deserialize_with = "present")]
+    pub closes_at: Option<Option<Time>>,
+    pub rejects_over_limit: Option<bool>,
+}
+
+/// Tells a field sent as `null` apart from one that was left out
+fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+impl PatchLocation {
+    pub fn apply(self, active: &mut ActiveModel) {
+        if let Some(name) = self.name {
+            active.name = Set(name);
+        }
+        if let Some(level) = self.level {
+            active.level = Set(level);
+        }
+        if let Some(is_work) = self.is_work {
+            active.is_work = Set(is_work);
+        }
+        if let Some(capacity) = self.capacity {
+            active.capacity = Set(capacity);
+        }
+        if let Some(opens_at) = self.opens_at {
+            active.opens_at = Set(opens_at);
+        }
+        if let Some(closes_at) = self.closes_at {
+            active.closes_at = Set(closes_at);
+        }
+        if let Some(rejects_over_limit) = self.rejects_over_limit {
+            active.rejects_over_limit = Set(rejects_over_limit);
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LocationOccupancy {
+    pub location: i32,
+    pub name: String,
+    pub occupancy: i64,
+    pub capacity: Option<i32>,
+    pub is_open: bool,
+    pub over_capacity: bool,
+}
+
+impl OrmSerializable for LocationOccupancy {}
+
+/// Whether the time falls between the two, which wrap past midnight when `end` is earlier than `start`.
+/// Any time is within an unbounded window.
+pub fn within_hours(start: Option<Time>, end: Option<Time>, time: Time) -> bool {
+    match (start, end) {
+        (Some(start), Some(end)) if start <= end => start <= time && time < end,
+        (Some(start), Some(end)) => time >= start || time < end,
+        _ => true,
+    }
+}
+
+impl Model {
+    pub fn is_open_at(&self, time: Time) -> bool {
+        within_hours(self.opens_at, self.closes_at, time)
+    }
+
+    /// Why one more resident can't come in at the given time, if there is a reason.
+    /// `occupancy` is how many residents are there already, None when it doesn't apply.
+    pub fn entry_violation(&self, time: Time, occupancy: Option<u64>) -> Option<String> {
+        if !self.is_open_at(time) {
+            return Some(format!("{} is closed", self.name));
+        }
+        match (self.capacity, occupancy) {
+            (Some(capacity), Some(occupancy)) if occupancy >= capacity.max(0) as u64 => {
+                Some(format!("{} is at capacity ({capacity})", self.name))
+            }
+            _ => None,
+        }
+    }
+
+    /// Residents currently at every location, against capacity and hours
+    pub async fn occupancy(db: &DatabaseConnection) -> Result<Vec<LocationOccupancy>, DbErr> {
+        let counts: std::collections::HashMap<i32, i64> = super::residents::Entity::find()
+            .select_only()
+            .column(super::residents::Column::CurrentLocation)
+            .column_as(super::residents::Column::Id.count(), "occupancy")
+            .filter(super::residents::Column::IsDeleted.eq(false))
+            .group_by(super::residents::Column::CurrentLocation)
+            .into_tuple::<(i32, i64)>()
+            .all(db)
+            .await?
+            .into_iter()
+            .collect();
+        let now = chrono::Local::now().time();
+        Ok(Entity::find()
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|location| {
+                let occupancy = counts.get(&location.id).copied().unwrap_or_default();
+                LocationOccupancy {
+                    is_open: location.is_open_at(now),
+                    over_capacity: location
+                        .capacity
+                        .is_some_and(|capacity| occupancy > i64::from(capacity)),
+                    location: location.id,
+                    name: location.name,
+                    occupancy,
+                    capacity: location.capacity,
+                }
+            })
+            .collect())
+    }
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
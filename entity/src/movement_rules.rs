@@ -0,0 +1,116 @@
+use crate::prelude::OrmSerializable;
+use crate::{locations, resident_restrictions, residents};
+use sea_orm::{entity::prelude::*, Condition, Set};
+use serde::{Deserialize, Serialize};
+
+impl OrmSerializable for Model {}
+
+/// Residents below `min_level` can't enter the location (or any location) during the window,
+/// or at all when the window isn't set
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "movement_rules")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub location: Option<i32>,
+    pub min_level: i32,
+    pub starts_at: Option<Time>,
+    pub ends_at: Option<Time>,
+    pub description: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PostMovementRule {
+    pub location: Option<i32>,
+    pub min_level: i32,
+    pub starts_at: Option<Time>,
+    pub ends_at: Option<Time>,
+    pub description: String,
+}
+
+impl PostMovementRule {
+    pub fn into_active_model(self) -> ActiveModel {
+        ActiveModel {
+            location: Set(self.location),
+            min_level: Set(self.min_level),
+            starts_at: Set(self.starts_at),
+            ends_at: Set(self.ends_at),
+            description: Set(self.description),
+            ..Default::default()
+        }
+    }
+}
+
+impl Model {
+    pub fn applies_at(&self, time: Time) -> bool {
+        locations::within_hours(self.starts_at, self.ends_at, time)
+    }
+}
+
+/// Decides whether the resident may move into the location at the given time, returning why not.
+/// A resident needs at least the location's level, every rule for the location in effect at that time,
+/// and no restriction keeping them out of it or away from a resident who is already there.
+pub async fn denial<C: ConnectionTrait>(
+    db: &C,
+    resident: &residents::Model,
+    location: &locations::Model,
+    ts: DateTime,
+) -> Result<Option<String>, DbErr> {
+    if resident.level < location.level {
+        return Ok(Some(format!(
+            "Level {} residents can't enter {} (level {})",
+            resident.level, location.name, location.level
+        )));
+    }
+    let rules = Entity::find()
+        .filter(
+            Condition::any()
+                .add(Column::Location.is_null())
+                .add(Column::Location.eq(location.id)),
+        )
+        .filter(Column::MinLevel.gt(resident.level))
+        .all(db)
+        .await?;
+    if let Some(rule) = rules.iter().find(|rule| rule.applies_at(ts.time())) {
+        return Ok(Some(rule.description.clone()));
+    }
+    for restriction in resident_restrictions::Model::active_for(db, resident.doc, ts).await? {
+        if restriction.location == Some(location.id) {
+            return Ok(Some(restriction.reason));
+        }
+        let Some(other) = restriction.other_resident(resident.doc) else {
+            continue;
+        };
+        let present = residents::Entity::find()
+            .filter(residents::Column::Doc.eq(other))
+            .filter(residents::Column::CurrentLocation.eq(location.id))
+            .filter(residents::Column::IsDeleted.eq(false))
+            .one(db)
+            .await?
+            .is_some();
+        if present {
+            return Ok(Some(restriction.reason));
+        }
+    }
+    Ok(None)
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::locations::Entity",
+        from = "Column::Location",
+        to = "super::locations::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Locations,
+}
+
+impl Related<super::locations::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Locations.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
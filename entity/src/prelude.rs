@@ -1,10 +1,26 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.6
 
 pub use super::accounts::Entity as Accounts;
+pub use super::auth_events::Entity as AuthEvents;
+pub use super::auth_sessions::Entity as AuthSessions;
+pub use super::callouts::Entity as Callouts;
+pub use super::devices::Entity as Devices;
+pub use super::headcounts::Entity as Headcounts;
+pub use super::ledger_postings::Entity as LedgerPostings;
+pub use super::levels::Entity as Levels;
 pub use super::locations::Entity as Locations;
+pub use super::movement_rules::Entity as MovementRules;
+pub use super::purchase_limits::Entity as PurchaseLimits;
+pub use super::purchase_order_lines::Entity as PurchaseOrderLines;
+pub use super::purchase_orders::Entity as PurchaseOrders;
+pub use super::rejected_scans::Entity as RejectedScans;
+pub use super::resident_restrictions::Entity as ResidentRestrictions;
 pub use super::residents::Entity as Residents;
 pub use super::residents::TimestampResident;
 pub use super::residents::UpdateResident;
+pub use super::stock_count_entries::Entity as StockCountEntries;
+pub use super::stock_counts::Entity as StockCounts;
+pub use super::timestamp_adjustments::Entity as TimestampAdjustments;
 pub use super::timestamps::Entity as Timestamps;
 pub use super::transaction_items::Entity as TransactionItems;
 pub use super::transactions::Entity as Transactions;
@@ -23,3 +39,5 @@ impl OrmSerializable for Accounts {}
 impl OrmSerializable for Transactions {}
 impl OrmSerializable for PostTransaction {}
 impl OrmSerializable for Users {}
+impl OrmSerializable for Levels {}
+impl OrmSerializable for Devices {}
@@ -0,0 +1,409 @@
+use crate::prelude::OrmSerializable;
+use crate::{residents, transaction_items, transactions};
+use chrono::{Datelike, Duration, NaiveTime};
+use sea_orm::{entity::prelude::*, Condition, QueryOrder, QuerySelect, Set};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+impl OrmSerializable for Model {}
+impl OrmSerializable for Allowance {}
+
+/// Caps what residents of a `level`, or one resident (`doc`), can buy. Without an `item_id` it caps the
+/// amount spent a week or a month, with one it caps how many of the item can be bought a week or
+/// restricts it entirely. A resident's own limit for the same item, or for the amounts, replaces only the caps
+/// of their level's that it sets itself, an item limit always deciding whether the item is restricted.
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "purchase_limits")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub level: Option<i32>,
+    pub doc: Option<i32>,
+    pub item_id: Option<i32>,
+    /// cents
+    pub weekly_cap: Option<i32>,
+    /// cents
+    pub monthly_cap: Option<i32>,
+    pub max_quantity: Option<i32>,
+    pub is_restricted: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PostPurchaseLimit {
+    pub level: Option<i32>,
+    pub doc: Option<i32>,
+    pub item_id: Option<i32>,
+    pub weekly_cap: Option<f64>,
+    pub monthly_cap: Option<f64>,
+    pub max_quantity: Option<i32>,
+    #[serde(default)]
+    pub is_restricted: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ItemAllowance {
+    pub item_id: i32,
+    pub is_restricted: bool,
+    pub max_quantity: Option<i32>,
+    pub bought: i64,
+    pub remaining: Option<i64>,
+}
+
+/// What the resident can still spend and buy this week and month, amounts in cents
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Allowance {
+    pub doc: i32,
+    pub week_start: DateTime,
+    pub month_start: DateTime,
+    pub weekly_cap: Option<i32>,
+    pub weekly_spent: i64,
+    pub weekly_remaining: Option<i64>,
+    pub monthly_cap: Option<i32>,
+    pub monthly_spent: i64,
+    pub monthly_remaining: Option<i64>,
+    pub items: Vec<ItemAllowance>,
+}
+
+impl PostPurchaseLimit {
+    /// A limit is for either a level or a resident, and has to limit something
+    pub fn is_valid(&self) -> bool {
+        let limits = match self.item_id {
+            Some(_) => self.max_quantity.is_some() || self.is_restricted,
+            None => self.weekly_cap.is_some() || self.monthly_cap.is_some(),
+        };
+        self.level.is_some() != self.doc.is_some() && limits
+    }
+
+    pub fn into_active_model(self) -> ActiveModel {
+        let cents = |amount: f64| (amount * 100.0).round() as i32;
+        ActiveModel {
+            level: Set(self.level),
+            doc: Set(self.doc),
+            item_id: Set(self.item_id),
+            weekly_cap: Set(self.weekly_cap.map(cents)),
+            monthly_cap: Set(self.monthly_cap.map(cents)),
+            max_quantity: Set(self.max_quantity),
+            is_restricted: Set(self.is_restricted),
+            ..Default::default()
+        }
+    }
+}
+
+/// Monday of this week and the first of this month, at midnight
+pub fn period_starts(now: DateTime) -> (DateTime, DateTime) {
+    let today = now.date();
+    let week = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+    let month = today.with_day(1).unwrap_or(today);
+    (
+        week.and_time(NaiveTime::MIN),
+        month.and_time(NaiveTime::MIN),
+    )
+}
+
+impl Model {
+    /// The limits the resident is held to
+    pub async fn applicable<C: ConnectionTrait>(
+        db: &C,
+        resident: &residents::Model,
+    ) -> Result<Vec<Self>, DbErr> {
+        let (own, level): (Vec<Self>, Vec<Self>) = Entity::find()
+            .filter(
+                Condition::any()
+                    .add(Column::Level.eq(resident.level))
+                    .add(Column::Doc.eq(resident.doc)),
+            )
+            .order_by_asc(Column::Id)
+            .all(db)
+            .await?
+            .into_iter()
+            .partition(|limit| limit.doc.is_some());
+        Ok(merge(level, own))
+    }
+}
+
+/// Clears what the resident's own limits override from their level's, the rest of both still applies
+fn merge(level: Vec<Model>, own: Vec<Model>) -> Vec<Model> {
+    let mut limits: Vec<Model> = level
+        .into_iter()
+        .map(|mut limit| {
+            for other in own.iter().filter(|other| other.item_id == limit.item_id) {
+                if other.weekly_cap.is_some() {
+                    limit.weekly_cap = None;
+                }
+                if other.monthly_cap.is_some() {
+                    limit.monthly_cap = None;
+                }
+                if other.max_quantity.is_some() {
+                    limit.max_quantity = None;
+                }
+                if other.item_id.is_some() {
+                    limit.is_restricted = false;
+                }
+            }
+            limit
+        })
+        .collect();
+    limits.extend(own);
+    limits
+}
+
+/// Purchases since `since` less what was voided or refunded of them, in cents and per item. Only
+/// reversals pointing back at one of those purchases count, and never for more than it spent.
+async fn spent_since<C: ConnectionTrait>(
+    db: &C,
+    account_id: i32,
+    since: DateTime,
+) -> Result<(i64, HashMap<i32, i64>), DbErr> {
+    let purchases: HashMap<i32, i64> = transactions::Entity::find()
+        .select_only()
+        .column(transactions::Column::Id)
+        .column(transactions::Column::Amount)
+        .filter(transactions::Column::AccountId.eq(account_id))
+        .filter(transactions::Column::Kind.eq("purchase"))
+        .filter(transactions::Column::Timestamp.gte(since))
+        .into_tuple::<(i32, i32)>()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|(id, amount)| (id, amount as i64))
+        .collect();
+    // reversal id -> the purchase it gives back
+    let reversals: HashMap<i32, (i32, i64)> = transactions::Entity::find()
+        .select_only()
+        .column(transactions::Column::Id)
+        .column(transactions::Column::OriginalId)
+        .column(transactions::Column::Amount)
+        .filter(transactions::Column::AccountId.eq(account_id))
+        .filter(transactions::Column::Kind.is_in(["void", "refund"]))
+        .filter(transactions::Column::OriginalId.is_in(purchases.keys().copied()))
+        .into_tuple::<(i32, i32, i32)>()
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|(id, original_id, amount)| (id, (original_id, amount as i64)))
+        .collect();
+    let mut reversed: HashMap<i32, i64> = HashMap::new();
+    for (original_id, amount) in reversals.values() {
+        *reversed.entry(*original_id).or_default() += amount;
+    }
+    let spent = purchases
+        .iter()
+        .map(|(id, amount)| amount - reversed.get(id).copied().unwrap_or(0).min(*amount))
+        .sum();
+    // (purchase, item) -> quantity still kept
+    let mut kept: HashMap<(i32, i32), i64> = HashMap::new();
+    for line in transaction_items::Entity::find()
+        .filter(
+            transaction_items::Column::TransactionId
+                .is_in(purchases.keys().chain(reversals.keys()).copied()),
+        )
+        .all(db)
+        .await?
+    {
+        let (purchase_id, sign) = match reversals.get(&line.transaction_id) {
+            Some((original_id, _)) => (*original_id, -1),
+            None => (line.transaction_id, 1),
+        };
+        *kept.entry((purchase_id, line.item_id)).or_default() += sign * line.quantity as i64;
+    }
+    let mut items = HashMap::new();
+    for ((_, item_id), quantity) in kept {
+        *items.entry(item_id).or_default() += quantity.max(0);
+    }
+    Ok((spent, items))
+}
+
+/// What the resident can still spend and buy, `account_id` is their commissary account if they have one
+pub async fn allowance<C: ConnectionTrait>(
+    db: &C,
+    resident: &residents::Model,
+    account_id: Option<i32>,
+    now: DateTime,
+) -> Result<Allowance, DbErr> {
+    let limits = Model::applicable(db, resident).await?;
+    let (week_start, month_start) = period_starts(now);
+    let ((weekly_spent, bought), (monthly_spent, _)) = match account_id {
+        Some(account_id) => (
+            spent_since(db, account_id, week_start).await?,
+            spent_since(db, account_id, month_start).await?,
+        ),
+        None => ((0, HashMap::new()), (0, HashMap::new())),
+    };
+    let amounts = || limits.iter().filter(|limit| limit.item_id.is_none());
+    let weekly_cap = amounts().filter_map(|limit| limit.weekly_cap).min();
+    let monthly_cap = amounts().filter_map(|limit| limit.monthly_cap).min();
+    let mut items: BTreeMap<i32, ItemAllowance> = BTreeMap::new();
+    for limit in &limits {
+        let Some(item_id) = limit.item_id else {
+            continue;
+        };
+        let item = items.entry(item_id).or_insert_with(|| ItemAllowance {
+            item_id,
+            is_restricted: false,
+            max_quantity: None,
+            bought: bought.get(&item_id).copied().unwrap_or(0),
+            remaining: None,
+        });
+        item.is_restricted |= limit.is_restricted;
+        item.max_quantity = item
+            .max_quantity
+            .into_iter()
+            .chain(limit.max_quantity)
+            .min();
+        item.remaining = if item.is_restricted {
+            Some(0)
+        } else {
+            item.max_quantity
+                .map(|max| (max as i64 - item.bought).max(0))
+        };
+    }
+    Ok(Allowance {
+        doc: resident.doc,
+        week_start,
+        month_start,
+        weekly_cap,
+        weekly_spent,
+        weekly_remaining: weekly_cap.map(|cap| (cap as i64 - weekly_spent).max(0)),
+        monthly_cap,
+        monthly_spent,
+        monthly_remaining: monthly_cap.map(|cap| (cap as i64 - monthly_spent).max(0)),
+        items: items.into_values().collect(),
+    })
+}
+
+/// Why the purchase of the (item, quantity) lines for `total` cents goes over the resident's limits
+pub async fn violation<C: ConnectionTrait>(
+    db: &C,
+    resident: &residents::Model,
+    account_id: i32,
+    lines: &[(i32, i32)],
+    total: i32,
+) -> Result<Option<String>, DbErr> {
+    let allowance = allowance(
+        db,
+        resident,
+        Some(account_id),
+        chrono::Local::now().naive_local(),
+    )
+    .await?;
+    let dollars = |cents: i64| format!("{:.2}", cents as f64 / 100.0);
+    if let Some(remaining) = allowance
+        .weekly_remaining
+        .filter(|remaining| *remaining < total as i64)
+    {
+        return Ok(Some(format!(
+            "Weekly spending limit reached, {} left",
+            dollars(remaining)
+        )));
+    }
+    if let Some(remaining) = allowance
+        .monthly_remaining
+        .filter(|remaining| *remaining < total as i64)
+    {
+        return Ok(Some(format!(
+            "Monthly spending limit reached, {} left",
+            dollars(remaining)
+        )));
+    }
+    for item in &allowance.items {
+        let quantity: i64 = lines
+            .iter()
+            .filter(|(item_id, _)| *item_id == item.item_id)
+            .map(|(_, quantity)| *quantity as i64)
+            .sum();
+        if quantity == 0 {
+            continue;
+        }
+        if item.is_restricted {
+            return Ok(Some(format!("Item {} is restricted", item.item_id)));
+        }
+        if let Some(remaining) = item.remaining.filter(|remaining| *remaining < quantity) {
+            return Ok(Some(format!(
+                "Only {} more of item {} can be bought this week",
+                remaining, item.item_id
+            )));
+        }
+    }
+    Ok(None)
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::items::Entity",
+        from = "Column::ItemId",
+        to = "super::items::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Items,
+}
+
+impl Related<super::items::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Items.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(doc: Option<i32>, item_id: Option<i32>) -> Model {
+        Model {
+            id: 0,
+            level: doc.is_none().then_some(1),
+            doc,
+            item_id,
+            weekly_cap: None,
+            monthly_cap: None,
+            max_quantity: None,
+            is_restricted: false,
+        }
+    }
+
+    #[test]
+    fn own_limit_keeps_the_caps_it_doesnt_set() {
+        let level = Model {
+            weekly_cap: Some(5000),
+            monthly_cap: Some(15000),
+            ..limit(None, None)
+        };
+        let own = Model {
+            monthly_cap: Some(20000),
+            ..limit(Some(7), None)
+        };
+        let limits = merge(vec![level], vec![own]);
+        assert_eq!(
+            limits.iter().filter_map(|limit| limit.weekly_cap).min(),
+            Some(5000)
+        );
+        assert_eq!(
+            limits.iter().filter_map(|limit| limit.monthly_cap).min(),
+            Some(20000)
+        );
+    }
+
+    #[test]
+    fn own_item_limit_lifts_the_restriction() {
+        let level = Model {
+            is_restricted: true,
+            ..limit(None, Some(3))
+        };
+        let other_item = Model {
+            max_quantity: Some(1),
+            ..limit(None, Some(4))
+        };
+        let own = Model {
+            max_quantity: Some(2),
+            ..limit(Some(7), Some(3))
+        };
+        let limits = merge(vec![level, other_item], vec![own]);
+        assert!(!limits.iter().any(|limit| limit.is_restricted));
+        assert!(limits
+            .iter()
+            .any(|limit| limit.item_id == Some(4) && limit.max_quantity == Some(1)));
+    }
+}
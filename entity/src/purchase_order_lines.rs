@@ -0,0 +1,85 @@
+use crate::prelude::OrmSerializable;
+use crate::purchase_orders;
+use sea_orm::{entity::prelude::*, QuerySelect};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+impl OrmSerializable for Model {}
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "purchase_order_lines")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub purchase_order_id: i32,
+    pub item_id: i32,
+    pub quantity_expected: i32,
+    pub quantity_received: i32,
+    /// cents
+    pub unit_cost: i32,
+}
+
+impl Model {
+    /// Received less expected, short deliveries are negative
+    pub fn discrepancy(&self) -> i32 {
+        self.quantity_received - self.quantity_expected
+    }
+}
+
+/// Per item, what's still to come in on orders that haven't been fully received, drafts included
+pub async fn outstanding<C: ConnectionTrait>(db: &C) -> Result<HashMap<i32, i64>, DbErr> {
+    let open: Vec<i32> = purchase_orders::Entity::find()
+        .select_only()
+        .column(purchase_orders::Column::Id)
+        .filter(purchase_orders::Column::Status.is_in([
+            purchase_orders::DRAFT,
+            purchase_orders::ORDERED,
+            purchase_orders::PARTIALLY_RECEIVED,
+        ]))
+        .into_tuple()
+        .all(db)
+        .await?;
+    let mut outstanding = HashMap::new();
+    for line in Entity::find()
+        .filter(Column::PurchaseOrderId.is_in(open))
+        .all(db)
+        .await?
+    {
+        *outstanding.entry(line.item_id).or_default() += (-line.discrepancy()).max(0) as i64;
+    }
+    Ok(outstanding)
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::purchase_orders::Entity",
+        from = "Column::PurchaseOrderId",
+        to = "super::purchase_orders::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    PurchaseOrders,
+    #[sea_orm(
+        belongs_to = "super::items::Entity",
+        from = "Column::ItemId",
+        to = "super::items::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    Items,
+}
+
+impl Related<super::purchase_orders::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PurchaseOrders.def()
+    }
+}
+
+impl Related<super::items::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Items.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
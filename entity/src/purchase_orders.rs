@@ -0,0 +1,338 @@
+use crate::prelude::OrmSerializable;
+use crate::{inventory_event, items, purchase_order_lines};
+use sea_orm::{
+    entity::prelude::*, sea_query::Expr, DatabaseTransaction, IntoActiveModel, PaginatorTrait,
+    QueryOrder, QuerySelect, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+impl OrmSerializable for Model {}
+impl OrmSerializable for PurchaseOrderDetail {}
+
+pub const DRAFT: &str = "draft";
+pub const ORDERED: &str = "ordered";
+pub const PARTIALLY_RECEIVED: &str = "partially_received";
+pub const RECEIVED: &str = "received";
+
+/// Stock ordered from a vendor, received into inventory as it arrives
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "purchase_orders")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub vendor: String,
+    /// draft, ordered, partially_received or received
+    pub status: String,
+    pub note: Option<String>,
+    pub created_by: Option<i32>,
+    pub created_at: DateTime,
+    pub ordered_at: Option<DateTime>,
+    pub received_at: Option<DateTime>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PostOrderLine {
+    pub item_id: i32,
+    pub quantity: i32,
+    pub unit_cost: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PostPurchaseOrder {
+    pub vendor: String,
+    pub note: Option<String>,
+    pub lines: Vec<PostOrderLine>,
+}
+
+/// A draft order for everything that's low, from one vendor
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PostReorder {
+    pub vendor: String,
+    pub note: Option<String>,
+    /// days of sales the suggestions are based on, 30 by default
+    pub days: Option<i64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReceivedLine {
+    pub line_id: i32,
+    pub quantity: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PostReceipt {
+    pub lines: Vec<ReceivedLine>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OrderLineDetail {
+    #[serde(flatten)]
+    pub line: purchase_order_lines::Model,
+    pub upc: String,
+    pub name: String,
+    /// received less expected
+    pub discrepancy: i32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PurchaseOrderDetail {
+    pub order: Model,
+    pub lines: Vec<OrderLineDetail>,
+    /// a received order where any line came in short or over
+    pub has_discrepancies: bool,
+}
+
+impl PostPurchaseOrder {
+    pub fn is_valid(&self) -> bool {
+        !self.vendor.trim().is_empty()
+            && !self.lines.is_empty()
+            && self
+                .lines
+                .iter()
+                .all(|line| line.quantity > 0 && line.unit_cost >= 0.0)
+    }
+
+    /// Stores the order and its lines as a draft, every item has to exist and not be deleted
+    pub async fn create(
+        self,
+        db: &DatabaseConnection,
+        user_id: Option<i32>,
+    ) -> Result<PurchaseOrderDetail, Box<dyn std::error::Error>> {
+        let txn = db.begin().await?;
+        let item_ids: HashSet<i32> = self.lines.iter().map(|line| line.item_id).collect();
+        let found = items::Entity::find()
+            .filter(items::Column::Id.is_in(item_ids.iter().copied()))
+            .filter(items::Column::IsDeleted.eq(false))
+            .count(&txn)
+            .await?;
+        if found != item_ids.len() as u64 {
+            return Err("Item not found".into());
+        }
+        let order = ActiveModel {
+            vendor: Set(self.vendor),
+            status: Set(DRAFT.to_string()),
+            note: Set(self.note),
+            created_by: Set(user_id),
+            created_at: Set(chrono::Local::now().naive_local()),
+            ordered_at: Set(None),
+            received_at: Set(None),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        purchase_order_lines::Entity::insert_many(self.lines.into_iter().map(|line| {
+            purchase_order_lines::ActiveModel {
+                purchase_order_id: Set(order.id),
+                item_id: Set(line.item_id),
+                quantity_expected: Set(line.quantity),
+                quantity_received: Set(0),
+                unit_cost: Set((line.unit_cost * 100.0).round() as i32),
+                ..Default::default()
+            }
+        }))
+        .exec(&txn)
+        .await?;
+        let detail = order.detail(&txn).await?;
+        txn.commit().await?;
+        Ok(detail)
+    }
+}
+
+impl PostReorder {
+    /// Drafts an order of each low item's suggested quantity, at what it cost on average
+    pub async fn create(
+        self,
+        db: &DatabaseConnection,
+        user_id: Option<i32>,
+    ) -> Result<PurchaseOrderDetail, Box<dyn std::error::Error>> {
+        let days = self.days.unwrap_or(30);
+        if days < 1 {
+            return Err("Sales have to be averaged over at least a day".into());
+        }
+        let lines: Vec<PostOrderLine> = items::low_stock(db, days)
+            .await?
+            .into_iter()
+            .filter(|low| low.suggested_quantity > 0)
+            .map(|low| PostOrderLine {
+                item_id: low.item.id,
+                quantity: low.suggested_quantity as i32,
+                unit_cost: low.item.unit_cost as f64 / 100.0,
+            })
+            .collect();
+        if lines.is_empty() {
+            return Err("Nothing needs reordering".into());
+        }
+        let order = PostPurchaseOrder {
+            vendor: self.vendor,
+            note: self.note,
+            lines,
+        };
+        if !order.is_valid() {
+            return Err("A purchase order needs a vendor".into());
+        }
+        order.create(db, user_id).await
+    }
+}
+
+impl Model {
+    /// The order with its lines and how far each is off what was expected
+    pub async fn detail<C: ConnectionTrait>(self, db: &C) -> Result<PurchaseOrderDetail, DbErr> {
+        let lines = purchase_order_lines::Entity::find()
+            .filter(purchase_order_lines::Column::PurchaseOrderId.eq(self.id))
+            .order_by_asc(purchase_order_lines::Column::Id)
+            .all(db)
+            .await?;
+        let items: HashMap<i32, items::Model> = items::Entity::find()
+            .filter(items::Column::Id.is_in(lines.iter().map(|line| line.item_id)))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|item| (item.id, item))
+            .collect();
+        let lines: Vec<OrderLineDetail> = lines
+            .into_iter()
+            .map(|line| {
+                let item = items.get(&line.item_id).cloned().unwrap_or_default();
+                OrderLineDetail {
+                    discrepancy: line.discrepancy(),
+                    upc: item.upc,
+                    name: item.name,
+                    line,
+                }
+            })
+            .collect();
+        Ok(PurchaseOrderDetail {
+            has_discrepancies: self.status == RECEIVED
+                && lines.iter().any(|line| line.discrepancy != 0),
+            order: self,
+            lines,
+        })
+    }
+
+    /// Locks the order so two receipts against it are applied one after the other
+    async fn lock(txn: &DatabaseTransaction, id: i32) -> Result<Self, Box<dyn std::error::Error>> {
+        Entity::find_by_id(id)
+            .lock_exclusive()
+            .one(txn)
+            .await?
+            .ok_or_else(|| "Purchase order not found".into())
+    }
+
+    /// Marks a draft as sent to the vendor, only ordered stock can be received
+    pub async fn place(
+        db: &DatabaseConnection,
+        id: i32,
+    ) -> Result<PurchaseOrderDetail, Box<dyn std::error::Error>> {
+        let txn = db.begin().await?;
+        let order = Self::lock(&txn, id).await?;
+        if order.status != DRAFT {
+            return Err("Only a draft can be ordered".into());
+        }
+        let mut order = order.into_active_model();
+        order.status = Set(ORDERED.to_string());
+        order.ordered_at = Set(Some(chrono::Local::now().naive_local()));
+        let detail = order.update(&txn).await?.detail(&txn).await?;
+        txn.commit().await?;
+        Ok(detail)
+    }
+
+    /// Adds the received quantities to the lines and to stock at the line's cost, with an inventory event for each.
+    /// The order is received once every line has come in, or when it's closed short, short or over lines
+    /// show as discrepancies.
+    pub async fn receive(
+        db: &DatabaseConnection,
+        id: i32,
+        receipt: &PostReceipt,
+    ) -> Result<PurchaseOrderDetail, Box<dyn std::error::Error>> {
+        let txn = db.begin().await?;
+        let order = Self::lock(&txn, id).await?;
+        if order.status != ORDERED && order.status != PARTIALLY_RECEIVED {
+            return Err(format!("A {} purchase order can't be received", order.status).into());
+        }
+        if receipt.lines.is_empty() || receipt.lines.iter().any(|line| line.quantity <= 0) {
+            return Err("A receipt needs a positive quantity for each line".into());
+        }
+        let mut lines: HashMap<i32, purchase_order_lines::Model> =
+            purchase_order_lines::Entity::find()
+                .filter(purchase_order_lines::Column::PurchaseOrderId.eq(id))
+                .all(&txn)
+                .await?
+                .into_iter()
+                .map(|line| (line.id, line))
+                .collect();
+        for received in &receipt.lines {
+            let Some(line) = lines.get_mut(&received.line_id) else {
+                return Err(
+                    format!("Line {} isn't on this purchase order", received.line_id).into(),
+                );
+            };
+            line.quantity_received += received.quantity;
+            purchase_order_lines::Entity::update_many()
+                .col_expr(
+                    purchase_order_lines::Column::QuantityReceived,
+                    Expr::value(line.quantity_received),
+                )
+                .filter(purchase_order_lines::Column::Id.eq(line.id))
+                .exec(&txn)
+                .await?;
+            inventory_event::ActiveModel {
+                item_id: Set(line.item_id),
+                quantity: Set(received.quantity),
+                is_add: Set(true),
+                purchase_order_id: Set(Some(id)),
+                unit_cost: Set(line.unit_cost),
+                created_at: Set(chrono::Local::now().naive_local()),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+            items::restock(&txn, line.item_id, received.quantity, line.unit_cost).await?;
+        }
+        let complete = lines
+            .values()
+            .all(|line| line.quantity_received >= line.quantity_expected);
+        let mut order = order.into_active_model();
+        if complete {
+            order.status = Set(RECEIVED.to_string());
+            order.received_at = Set(Some(chrono::Local::now().naive_local()));
+        } else {
+            order.status = Set(PARTIALLY_RECEIVED.to_string());
+        }
+        let detail = order.update(&txn).await?.detail(&txn).await?;
+        txn.commit().await?;
+        Ok(detail)
+    }
+
+    /// Receives an order the vendor won't send the rest of, the lines still short show as discrepancies
+    pub async fn close_short(
+        db: &DatabaseConnection,
+        id: i32,
+    ) -> Result<PurchaseOrderDetail, Box<dyn std::error::Error>> {
+        let txn = db.begin().await?;
+        let order = Self::lock(&txn, id).await?;
+        if order.status != ORDERED && order.status != PARTIALLY_RECEIVED {
+            return Err(format!("A {} purchase order can't be closed", order.status).into());
+        }
+        let mut order = order.into_active_model();
+        order.status = Set(RECEIVED.to_string());
+        order.received_at = Set(Some(chrono::Local::now().naive_local()));
+        let detail = order.update(&txn).await?.detail(&txn).await?;
+        txn.commit().await?;
+        Ok(detail)
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::purchase_order_lines::Entity")]
+    PurchaseOrderLines,
+}
+
+impl Related<super::purchase_order_lines::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PurchaseOrderLines.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
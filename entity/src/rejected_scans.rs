@@ -0,0 +1,41 @@
+use crate::prelude::OrmSerializable;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+impl OrmSerializable for Model {}
+
+/// A scan that was denied, the resident's location was left alone
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "rejected_scans")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub doc: i32,
+    /// where the resident tried to go
+    pub location: i32,
+    pub ts: DateTime,
+    pub device_id: Option<i32>,
+    pub reason: String,
+    /// key of the scan that was denied, so resending it isn't kept twice
+    pub idempotency_key: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::residents::Entity",
+        from = "Column::Doc",
+        to = "super::residents::Column::Doc",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Residents,
+}
+
+impl Related<super::residents::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Residents.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
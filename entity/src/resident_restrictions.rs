@@ -0,0 +1,94 @@
+use crate::prelude::OrmSerializable;
+use sea_orm::{entity::prelude::*, Condition, Set};
+use serde::{Deserialize, Serialize};
+
+impl OrmSerializable for Model {}
+
+/// Keeps one resident out of a location, or away from another resident wherever they are
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "resident_restrictions")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub doc: i32,
+    pub location: Option<i32>,
+    /// doc of a resident to keep separate from, the restriction applies to both of them
+    pub separate_from: Option<i32>,
+    pub reason: String,
+    pub expires_at: Option<DateTime>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PostRestriction {
+    pub location: Option<i32>,
+    pub separate_from: Option<i32>,
+    pub reason: String,
+    pub expires_at: Option<DateTime>,
+}
+
+impl PostRestriction {
+    /// A restriction needs either a location or a resident to keep apart from
+    pub fn is_valid(&self) -> bool {
+        self.location.is_some() != self.separate_from.is_some() && !self.reason.trim().is_empty()
+    }
+
+    pub fn into_active_model(self, doc: i32) -> ActiveModel {
+        ActiveModel {
+            doc: Set(doc),
+            location: Set(self.location),
+            separate_from: Set(self.separate_from),
+            reason: Set(self.reason),
+            expires_at: Set(self.expires_at),
+            ..Default::default()
+        }
+    }
+}
+
+impl Model {
+    /// Restrictions on the resident, including keep-separate orders naming them, in effect at `ts`
+    pub async fn active_for<C: ConnectionTrait>(
+        db: &C,
+        doc: i32,
+        ts: DateTime,
+    ) -> Result<Vec<Self>, DbErr> {
+        Entity::find()
+            .filter(
+                Condition::any()
+                    .add(Column::Doc.eq(doc))
+                    .add(Column::SeparateFrom.eq(doc)),
+            )
+            .filter(
+                Condition::any()
+                    .add(Column::ExpiresAt.is_null())
+                    .add(Column::ExpiresAt.gt(ts)),
+            )
+            .all(db)
+            .await
+    }
+
+    /// The resident the given one has to be kept apart from
+    pub fn other_resident(&self, doc: i32) -> Option<i32> {
+        let other = self.separate_from?;
+        Some(if other == doc { self.doc } else { other })
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::residents::Entity",
+        from = "Column::Doc",
+        to = "super::residents::Column::Doc",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Residents,
+}
+
+impl Related<super::residents::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Residents.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,51 @@
+use crate::prelude::OrmSerializable;
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+
+impl OrmSerializable for Model {}
+
+/// How many of one item were counted, and what was on hand when it was first counted
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "stock_count_entries")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub stock_count_id: i32,
+    pub item_id: i32,
+    pub counted: i32,
+    /// items.quantity when the item was first counted
+    pub on_hand: Option<i32>,
+    pub counted_at: DateTime,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::stock_counts::Entity",
+        from = "Column::StockCountId",
+        to = "super::stock_counts::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    StockCounts,
+    #[sea_orm(
+        belongs_to = "super::items::Entity",
+        from = "Column::ItemId",
+        to = "super::items::Column::Id"
+    )]
+    Items,
+}
+
+impl Related<super::stock_counts::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::StockCounts.def()
+    }
+}
+
+impl Related<super::items::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Items.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
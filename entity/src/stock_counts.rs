@@ -0,0 +1,291 @@
+use crate::prelude::OrmSerializable;
+use crate::{inventory_event, items, stock_count_entries};
+use sea_orm::{
+    entity::prelude::*, sea_query::OnConflict, IntoActiveModel, QueryOrder, QuerySelect, Set,
+    TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+impl OrmSerializable for Model {}
+impl OrmSerializable for StockCountReport {}
+
+/// A physical count of some or all of the stock, posted once to bring `items.quantity` in line with it
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "stock_counts")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub note: Option<String>,
+    pub started_by: Option<i32>,
+    pub started_at: DateTime,
+    /// counts are only taken until the count is posted
+    pub posted_at: Option<DateTime>,
+    /// the reason recorded on the adjustments it posted
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PostStockCount {
+    pub note: Option<String>,
+}
+
+/// A scanned UPC counts one more of the item, a `quantity` replaces what was counted so far
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CountedItem {
+    pub upc: String,
+    pub quantity: Option<i32>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PostCount {
+    /// one of the inventory event reasons, `cycle_count` when not given
+    pub reason: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StockCountLine {
+    pub item_id: i32,
+    pub upc: String,
+    pub name: String,
+    pub counted: i32,
+    /// the stock when the item was first counted
+    pub on_hand: i32,
+    /// counted less on hand
+    pub variance: i32,
+    /// variance at the item's unit cost, in cents
+    pub variance_value: i64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StockCountReport {
+    pub stock_count: Model,
+    pub lines: Vec<StockCountLine>,
+    pub variance_value: i64,
+}
+
+#[derive(Clone, Debug)]
+pub enum StockScanOutcome {
+    Counted(stock_count_entries::Model),
+    Posted,
+    NotFound,
+}
+
+impl PostStockCount {
+    pub async fn start(
+        self,
+        db: &DatabaseConnection,
+        user_id: Option<i32>,
+    ) -> Result<Model, DbErr> {
+        ActiveModel {
+            note: Set(self.note),
+            started_by: Set(user_id),
+            started_at: Set(chrono::Local::now().naive_local()),
+            posted_at: Set(None),
+            reason: Set(None),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+}
+
+impl Model {
+    pub fn is_open(&self) -> bool {
+        self.posted_at.is_none()
+    }
+
+    /// Records a scan or an entered quantity, two scanners counting the same item add up.
+    /// The stock on hand is noted the first time the item is counted, what sells or arrives after
+    /// that isn't on the shelf being counted.
+    pub async fn count(
+        &self,
+        db: &DatabaseConnection,
+        counted: &CountedItem,
+    ) -> Result<StockScanOutcome, DbErr> {
+        if !self.is_open() {
+            return Ok(StockScanOutcome::Posted);
+        }
+        let Some(item) = items::Entity::find()
+            .filter(items::Column::Upc.eq(&counted.upc))
+            .filter(items::Column::IsDeleted.eq(false))
+            .one(db)
+            .await?
+        else {
+            return Ok(StockScanOutcome::NotFound);
+        };
+        let mut on_conflict = OnConflict::columns([
+            stock_count_entries::Column::StockCountId,
+            stock_count_entries::Column::ItemId,
+        ]);
+        match counted.quantity {
+            Some(_) => on_conflict.update_column(stock_count_entries::Column::Counted),
+            None => on_conflict.value(
+                stock_count_entries::Column::Counted,
+                Expr::col((
+                    stock_count_entries::Entity,
+                    stock_count_entries::Column::Counted,
+                ))
+                .add(1),
+            ),
+        };
+        let entry = stock_count_entries::Entity::insert(stock_count_entries::ActiveModel {
+            stock_count_id: Set(self.id),
+            item_id: Set(item.id),
+            counted: Set(counted.quantity.unwrap_or(1)),
+            on_hand: Set(Some(item.quantity)),
+            counted_at: Set(chrono::Local::now().naive_local()),
+            ..Default::default()
+        })
+        .on_conflict(
+            on_conflict
+                .update_column(stock_count_entries::Column::CountedAt)
+                .to_owned(),
+        )
+        .exec_with_returning(db)
+        .await?;
+        Ok(StockScanOutcome::Counted(entry))
+    }
+
+    /// Each counted item against what was on hand when it was counted
+    pub async fn report<C: ConnectionTrait>(self, db: &C) -> Result<StockCountReport, DbErr> {
+        let entries = stock_count_entries::Entity::find()
+            .filter(stock_count_entries::Column::StockCountId.eq(self.id))
+            .order_by_asc(stock_count_entries::Column::ItemId)
+            .all(db)
+            .await?;
+        let items: HashMap<i32, items::Model> = items::Entity::find()
+            .filter(items::Column::Id.is_in(entries.iter().map(|entry| entry.item_id)))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|item| (item.id, item))
+            .collect();
+        let lines: Vec<StockCountLine> = entries
+            .into_iter()
+            .map(|entry| {
+                let item = items.get(&entry.item_id).cloned().unwrap_or_default();
+                let on_hand = entry.on_hand.unwrap_or(item.quantity);
+                let variance = entry.counted - on_hand;
+                StockCountLine {
+                    item_id: entry.item_id,
+                    upc: item.upc,
+                    name: item.name,
+                    counted: entry.counted,
+                    on_hand,
+                    variance,
+                    variance_value: variance as i64 * item.unit_cost as i64,
+                }
+            })
+            .collect();
+        Ok(StockCountReport {
+            variance_value: lines.iter().map(|line| line.variance_value).sum(),
+            stock_count: self,
+            lines,
+        })
+    }
+
+    /// Adjusts every counted item's stock by what the count was off, writing an inventory event for each
+    /// one. Sales and receipts since the item was counted are kept, items that weren't counted are left alone.
+    pub async fn post(
+        db: &DatabaseConnection,
+        id: i32,
+        posting: &PostCount,
+    ) -> Result<StockCountReport, Box<dyn std::error::Error>> {
+        let reason = posting.reason.as_deref().unwrap_or("cycle_count");
+        if !inventory_event::REASONS.contains(&reason) {
+            return Err(format!("Unknown adjustment reason: {reason}").into());
+        }
+        let txn = db.begin().await?;
+        let Some(count) = Entity::find_by_id(id).lock_exclusive().one(&txn).await? else {
+            return Err("Stock count not found".into());
+        };
+        if !count.is_open() {
+            return Err("This stock count was already posted".into());
+        }
+        let entries = stock_count_entries::Entity::find()
+            .filter(stock_count_entries::Column::StockCountId.eq(id))
+            .all(&txn)
+            .await?;
+        if entries.is_empty() {
+            return Err("Nothing has been counted yet".into());
+        }
+        // locked in id order like purchases, so sales wait for the count rather than being lost in it
+        let items: HashMap<i32, items::Model> = items::Entity::find()
+            .filter(items::Column::Id.is_in(entries.iter().map(|entry| entry.item_id)))
+            .order_by_asc(items::Column::Id)
+            .lock_exclusive()
+            .all(&txn)
+            .await?
+            .into_iter()
+            .map(|item| (item.id, item))
+            .collect();
+        let now = chrono::Local::now().naive_local();
+        for entry in entries {
+            let Some(item) = items.get(&entry.item_id) else {
+                continue;
+            };
+            // counted before the stock was noted on the count
+            let on_hand = entry.on_hand.unwrap_or(item.quantity);
+            let variance = entry.counted - on_hand;
+            if entry.on_hand.is_none() {
+                let mut entry = entry.into_active_model();
+                entry.on_hand = Set(Some(on_hand));
+                entry.update(&txn).await?;
+            }
+            if variance == 0 {
+                continue;
+            }
+            inventory_event::ActiveModel {
+                item_id: Set(item.id),
+                quantity: Set(variance.abs()),
+                is_add: Set(variance > 0),
+                purchase_order_id: Set(None),
+                unit_cost: Set(item.unit_cost),
+                created_at: Set(now),
+                reason: Set(Some(reason.to_string())),
+                ..Default::default()
+            }
+            .insert(&txn)
+            .await?;
+            let quantity = item.quantity + variance;
+            let mut item = item.clone().into_active_model();
+            item.quantity = Set(quantity);
+            item.update(&txn).await?;
+        }
+        let mut count = count.into_active_model();
+        count.posted_at = Set(Some(now));
+        count.reason = Set(Some(reason.to_string()));
+        let report = count.update(&txn).await?.report(&txn).await?;
+        txn.commit().await?;
+        Ok(report)
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::stock_count_entries::Entity")]
+    Entries,
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::StartedBy",
+        to = "super::users::Column::Id",
+        on_update = "NoAction",
+        on_delete = "SetNull"
+    )]
+    Users,
+}
+
+impl Related<super::stock_count_entries::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Entries.def()
+    }
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,228 @@
+use crate::prelude::OrmSerializable;
+use crate::timestamps;
+use sea_orm::{
+    entity::prelude::*, ConnectionTrait, IntoActiveModel, QueryOrder, Set, TransactionTrait,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+impl OrmSerializable for Model {}
+
+/// One manual change to a timestamp, keeping the values it had before
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize, Deserialize)]
+#[sea_orm(table_name = "timestamp_adjustments")]
+pub struct Model {
+    #[sea_orm(primary_key, auto_increment = true)]
+    pub id: i32,
+    pub timestamp_id: i32,
+    pub user_id: i32,
+    /// insert, edit or void
+    pub action: String,
+    pub reason: String,
+    pub old_location: Option<i32>,
+    pub old_ts: Option<DateTime>,
+    pub new_location: Option<i32>,
+    pub new_ts: Option<DateTime>,
+    pub created_at: DateTime,
+}
+
+/// A missed scan entered by a supervisor
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InsertCorrection {
+    pub doc: i32,
+    pub location: i32,
+    pub ts: DateTime,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EditCorrection {
+    pub location: Option<i32>,
+    pub ts: Option<DateTime>,
+    pub reason: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VoidCorrection {
+    pub reason: String,
+}
+
+impl Model {
+    /// Ids of the given timestamps that have been inserted, edited or voided by hand
+    pub async fn adjusted_ids(
+        db: &DatabaseConnection,
+        ids: Vec<i32>,
+    ) -> Result<HashSet<i32>, DbErr> {
+        Ok(Entity::find()
+            .filter(Column::TimestampId.is_in(ids))
+            .all(db)
+            .await?
+            .into_iter()
+            .map(|adjustment| adjustment.timestamp_id)
+            .collect())
+    }
+
+    pub async fn for_timestamp(
+        db: &DatabaseConnection,
+        timestamp_id: i32,
+    ) -> Result<Vec<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::TimestampId.eq(timestamp_id))
+            .order_by_asc(Column::CreatedAt)
+            .all(db)
+            .await
+    }
+
+    async fn log<C: ConnectionTrait>(
+        db: &C,
+        timestamp_id: i32,
+        user_id: i32,
+        action: &str,
+        reason: &str,
+        old: Option<&timestamps::Model>,
+        new: Option<&timestamps::Model>,
+    ) -> Result<Self, DbErr> {
+        ActiveModel {
+            timestamp_id: Set(timestamp_id),
+            user_id: Set(user_id),
+            action: Set(action.to_string()),
+            reason: Set(reason.trim().to_string()),
+            old_location: Set(old.map(|ts| ts.location)),
+            old_ts: Set(old.map(|ts| ts.ts)),
+            new_location: Set(new.map(|ts| ts.location)),
+            new_ts: Set(new.map(|ts| ts.ts)),
+            created_at: Set(chrono::Local::now().naive_local()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+    }
+}
+
+impl InsertCorrection {
+    pub async fn apply(
+        &self,
+        db: &DatabaseConnection,
+        user_id: i32,
+    ) -> Result<timestamps::Model, DbErr> {
+        let txn = db.begin().await?;
+        let inserted = timestamps::ActiveModel {
+            doc: Set(self.doc),
+            location: Set(self.location),
+            ts: Set(self.ts),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        Model::log(
+            &txn,
+            inserted.id,
+            user_id,
+            "insert",
+            &self.reason,
+            None,
+            Some(&inserted),
+        )
+        .await?;
+        timestamps::Model::refresh_current_location(&txn, self.doc).await?;
+        crate::callouts::Model::rematch(&txn, self.doc, inserted.ts).await?;
+        txn.commit().await?;
+        Ok(inserted)
+    }
+}
+
+impl EditCorrection {
+    pub async fn apply(
+        &self,
+        db: &DatabaseConnection,
+        timestamp: timestamps::Model,
+        user_id: i32,
+    ) -> Result<timestamps::Model, DbErr> {
+        let txn = db.begin().await?;
+        let mut active = timestamp.clone().into_active_model();
+        if let Some(location) = self.location {
+            active.location = Set(location);
+        }
+        if let Some(ts) = self.ts {
+            active.ts = Set(ts);
+        }
+        let updated = active.update(&txn).await?;
+        Model::log(
+            &txn,
+            updated.id,
+            user_id,
+            "edit",
+            &self.reason,
+            Some(&timestamp),
+            Some(&updated),
+        )
+        .await?;
+        timestamps::Model::refresh_current_location(&txn, updated.doc).await?;
+        crate::callouts::Model::rematch(&txn, updated.doc, timestamp.ts.min(updated.ts)).await?;
+        txn.commit().await?;
+        Ok(updated)
+    }
+}
+
+impl VoidCorrection {
+    /// Voided timestamps are kept for the audit trail but left out of sessions and hours
+    pub async fn apply(
+        &self,
+        db: &DatabaseConnection,
+        timestamp: timestamps::Model,
+        user_id: i32,
+    ) -> Result<timestamps::Model, DbErr> {
+        let txn = db.begin().await?;
+        let mut active = timestamp.clone().into_active_model();
+        active.is_voided = Set(true);
+        let voided = active.update(&txn).await?;
+        Model::log(
+            &txn,
+            voided.id,
+            user_id,
+            "void",
+            &self.reason,
+            Some(&timestamp),
+            None,
+        )
+        .await?;
+        timestamps::Model::refresh_current_location(&txn, voided.doc).await?;
+        crate::callouts::Model::rematch(&txn, voided.doc, voided.ts).await?;
+        txn.commit().await?;
+        Ok(voided)
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::timestamps::Entity",
+        from = "Column::TimestampId",
+        to = "super::timestamps::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Timestamps,
+    #[sea_orm(
+        belongs_to = "super::users::Entity",
+        from = "Column::UserId",
+        to = "super::users::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    Users,
+}
+
+impl Related<super::timestamps::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Timestamps.def()
+    }
+}
+
+impl Related<super::users::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Users.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
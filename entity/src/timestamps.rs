@@ -1,11 +1,17 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.6
 
 use crate::prelude::OrmSerializable;
-use sea_orm::entity::prelude::*;
+use sea_orm::{
+    entity::prelude::*, DatabaseTransaction, IntoActiveModel, PaginatorTrait, QueryOrder,
+    QuerySelect, Set, SqlErr, TransactionTrait,
+};
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 impl OrmSerializable for Model {}
 
+/// Scans stamped further than this ahead of the server clock are rejected
+const MAX_CLOCK_SKEW_MINUTES: i64 = 5;
+
 #[derive(
     Clone, Debug, PartialEq, DeriveEntityModel, DeriveRelatedEntity, Eq, Serialize, Deserialize,
 )]
@@ -16,11 +22,17 @@ pub struct Model {
     pub doc: i32,
     pub location: i32,
     pub ts: DateTime,
+    pub device_id: Option<i32>,
+    pub idempotency_key: Option<String>,
+    /// voided by a supervisor, kept for the audit trail
+    #[serde(default)]
+    pub is_voided: bool,
 }
 
 impl OrmSerializable for ResidentTimestamp {}
 impl OrmSerializable for sea_orm::JsonValue {}
 impl OrmSerializable for PostTimestamp {}
+impl OrmSerializable for BatchScanResult {}
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct ResidentTimestamp {
@@ -55,6 +67,324 @@ impl From<(crate::residents::Model, Model)> for ResidentTimestamp {
 pub struct PostTimestamp {
     pub rfid: String,
     pub location: i32,
+    /// when the card was scanned, for scans buffered by a client while offline
+    #[serde(default)]
+    pub ts: Option<DateTime>,
+    /// client generated key so a resent scan is only recorded once
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+/// A scan sent by a registered device, which supplies the location itself
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeviceScan {
+    pub rfid: String,
+    #[serde(default)]
+    pub ts: Option<DateTime>,
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+}
+
+impl DeviceScan {
+    pub fn at_location(self, location: i32) -> PostTimestamp {
+        PostTimestamp {
+            rfid: self.rfid,
+            location,
+            ts: self.ts,
+            idempotency_key: self.idempotency_key,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum ScanOutcome {
+    Recorded(ScanResult),
+    /// the move isn't allowed, or the location is full or closed and rejects scans over its limits
+    Rejected(String),
+    /// the idempotency key was already recorded, holds the original timestamp
+    Duplicate(Model),
+    NotFound,
+    InFuture,
+}
+
+/// Per entry result of a batch upload, in the order the entries were sent
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BatchScanResult {
+    pub rfid: String,
+    pub idempotency_key: Option<String>,
+    pub status: String,
+    pub timestamp: Option<Model>,
+    /// why the scan was rejected, or what was wrong with it when it was recorded anyway
+    pub warnings: Vec<String>,
+}
+
+impl BatchScanResult {
+    pub fn new(scan: &PostTimestamp, outcome: &ScanOutcome) -> Self {
+        let (status, timestamp) = match outcome {
+            ScanOutcome::Recorded(result) => ("recorded", Some(result.timestamp.clone())),
+            ScanOutcome::Rejected(_) => ("rejected", None),
+            ScanOutcome::Duplicate(existing) => ("duplicate", Some(existing.clone())),
+            ScanOutcome::NotFound => ("resident_not_found", None),
+            ScanOutcome::InFuture => ("timestamp_in_future", None),
+        };
+        let warnings = match outcome {
+            ScanOutcome::Recorded(result) => result.warnings.clone(),
+            ScanOutcome::Rejected(reason) => vec![reason.clone()],
+            _ => vec![],
+        };
+        Self {
+            rfid: scan.rfid.clone(),
+            idempotency_key: scan.idempotency_key.clone(),
+            status: status.to_string(),
+            timestamp,
+            warnings,
+        }
+    }
+}
+
+/// A resident toggled in or out of a location by a scan
+#[derive(Clone, Debug)]
+pub struct ScanResult {
+    pub resident: crate::residents::Model,
+    pub from_location: i32,
+    pub timestamp: Model,
+    /// limits of the location that were exceeded, for locations that only warn
+    pub warnings: Vec<String>,
+}
+
+impl From<&ScanResult> for ResidentTimestamp {
+    fn from(scan: &ScanResult) -> Self {
+        ResidentTimestamp {
+            id: scan.resident.id,
+            doc: scan.resident.doc,
+            name: scan.resident.name.clone(),
+            location: scan.timestamp.location,
+            ts: scan.timestamp.ts,
+        }
+    }
+}
+
+impl PostTimestamp {
+    /// Moves the resident to the scanned location and stores the timestamp, scanning at the
+    /// location the resident was already at signs them out (location 0).
+    /// A scan older than the resident's latest timestamp is toggled against where they were at
+    /// that time and leaves their current location alone.
+    /// The resident is locked until the scan is stored, so their scans toggle one after the other and
+    /// the timestamp, their location and their callouts are only ever written together.
+    #[rustfmt::skip]
+    pub async fn record(&self, db: &DatabaseConnection, device: Option<i32>) -> Result<ScanOutcome, DbErr> {
+        if let Some(existing) = self.find_duplicate(db).await? {
+            return Ok(ScanOutcome::Duplicate(existing));
+        }
+        if let Some(rejected) = self.find_rejected(db).await? {
+            return Ok(ScanOutcome::Rejected(rejected.reason));
+        }
+        let now = chrono::Local::now().naive_local();
+        let ts = self.ts.unwrap_or(now);
+        if ts > now + chrono::Duration::minutes(MAX_CLOCK_SKEW_MINUTES) {
+            return Ok(ScanOutcome::InFuture);
+        }
+        let txn = db.begin().await?;
+        let Some(resident) = crate::residents::Entity::find()
+            .filter(crate::residents::Column::Rfid.eq(&self.rfid))
+            .filter(crate::residents::Column::IsDeleted.eq(false))
+            .lock_exclusive()
+            .one(&txn)
+            .await?
+        else {
+            return Ok(ScanOutcome::NotFound);
+        };
+        let is_latest = Entity::find()
+            .filter(Column::Doc.eq(resident.doc))
+            .filter(Column::IsVoided.eq(false))
+            .filter(Column::Ts.gt(ts))
+            .one(&txn)
+            .await?
+            .is_none();
+        let from_location = if is_latest {
+            resident.current_location
+        } else {
+            Entity::find()
+                .filter(Column::Doc.eq(resident.doc))
+                .filter(Column::IsVoided.eq(false))
+                .filter(Column::Ts.lte(ts))
+                .order_by_desc(Column::Ts)
+                .one(&txn)
+                .await?
+                .map_or(0, |prior| prior.location)
+        };
+        let location = if self.location == from_location { 0 } else { self.location };
+        let mut warnings = vec![];
+        // signing out is always allowed
+        if location != 0 {
+            if let Some(destination) = crate::locations::Entity::find_by_id(location).one(&txn).await? {
+                if let Some(reason) = crate::movement_rules::denial(&txn, &resident, &destination, ts).await? {
+                    return self.reject(db, txn, resident.doc, ts, device, reason).await;
+                }
+                match Self::entry_violation(&txn, &destination, ts, is_latest.then_some(resident.doc)).await? {
+                    Some(reason) if destination.rejects_over_limit => return self.reject(db, txn, resident.doc, ts, device, reason).await,
+                    Some(reason) => warnings.push(reason),
+                    None => {}
+                }
+            }
+        }
+        let inserted = ActiveModel {
+            doc: Set(resident.doc),
+            location: Set(location),
+            ts: Set(ts),
+            device_id: Set(device),
+            idempotency_key: Set(self.idempotency_key.clone()),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await;
+        let timestamp = match inserted {
+            Ok(timestamp) => timestamp,
+            // the same scan was resent while the first upload was still being recorded
+            Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+                txn.rollback().await?;
+                if let Some(existing) = self.find_duplicate(db).await? {
+                    return Ok(ScanOutcome::Duplicate(existing));
+                }
+                return Err(e);
+            }
+            Err(e) => return Err(e),
+        };
+        let resident = if is_latest {
+            let mut resident = resident.into_active_model();
+            resident.current_location = Set(location);
+            resident.update(&txn).await?
+        } else {
+            resident
+        };
+        crate::callouts::Model::match_scan(&txn, &timestamp, from_location).await?;
+        txn.commit().await?;
+        Ok(ScanOutcome::Recorded(ScanResult { resident, from_location, timestamp, warnings }))
+    }
+
+    /// Checks a move into the location against its hours, and against its capacity when `entering` holds
+    /// the doc of a resident moving there now rather than a backdated scan.
+    #[rustfmt::skip]
+    async fn entry_violation<C: ConnectionTrait>(db: &C, location: &crate::locations::Model, ts: DateTime, entering: Option<i32>) -> Result<Option<String>, DbErr> {
+        let occupancy = match entering {
+            Some(doc) if location.capacity.is_some() => Some(
+                crate::residents::Entity::find()
+                    .filter(crate::residents::Column::CurrentLocation.eq(location.id))
+                    .filter(crate::residents::Column::IsDeleted.eq(false))
+                    .filter(crate::residents::Column::Doc.ne(doc))
+                    .count(db)
+                    .await?,
+            ),
+            _ => None,
+        };
+        Ok(location.entry_violation(ts.time(), occupancy))
+    }
+
+    /// Keeps the denied attempt for staff to review, the resident stays where they were
+    async fn reject(
+        &self,
+        db: &DatabaseConnection,
+        txn: DatabaseTransaction,
+        doc: i32,
+        ts: DateTime,
+        device: Option<i32>,
+        reason: String,
+    ) -> Result<ScanOutcome, DbErr> {
+        let inserted = crate::rejected_scans::ActiveModel {
+            doc: Set(doc),
+            location: Set(self.location),
+            ts: Set(ts),
+            device_id: Set(device),
+            reason: Set(reason.clone()),
+            idempotency_key: Set(self.idempotency_key.clone()),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await;
+        match inserted {
+            Ok(_) => {
+                txn.commit().await?;
+                Ok(ScanOutcome::Rejected(reason))
+            }
+            // the same scan was resent while the first upload was still being rejected
+            Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+                txn.rollback().await?;
+                match self.find_rejected(db).await? {
+                    Some(rejected) => Ok(ScanOutcome::Rejected(rejected.reason)),
+                    None => Err(e),
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// A resent scan that was denied the first time is denied again without keeping another attempt
+    async fn find_rejected(
+        &self,
+        db: &DatabaseConnection,
+    ) -> Result<Option<crate::rejected_scans::Model>, DbErr> {
+        match &self.idempotency_key {
+            Some(key) => {
+                crate::rejected_scans::Entity::find()
+                    .filter(crate::rejected_scans::Column::IdempotencyKey.eq(key))
+                    .one(db)
+                    .await
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn find_duplicate(&self, db: &DatabaseConnection) -> Result<Option<Model>, DbErr> {
+        match &self.idempotency_key {
+            Some(key) => {
+                Entity::find()
+                    .filter(Column::IdempotencyKey.eq(key))
+                    .one(db)
+                    .await
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Replays buffered scans oldest first, so each one toggles against the scans before it.
+    /// Returns the outcomes in the order the scans were given.
+    #[rustfmt::skip]
+    pub async fn record_batch(scans: &[Self], db: &DatabaseConnection, device: Option<i32>) -> Result<Vec<ScanOutcome>, DbErr> {
+        let mut order: Vec<usize> = (0..scans.len()).collect();
+        // scans without a time were taken just now, so they go last
+        order.sort_by_key(|&i| (scans[i].ts.is_none(), scans[i].ts));
+        let mut outcomes = vec![ScanOutcome::NotFound; scans.len()];
+        for i in order {
+            outcomes[i] = scans[i].record(db, device).await?;
+        }
+        Ok(outcomes)
+    }
+}
+
+impl Model {
+    /// Points the resident at the location of their latest timestamp that still counts,
+    /// after one was inserted, moved or voided by hand
+    pub async fn refresh_current_location<C: ConnectionTrait>(
+        db: &C,
+        doc: i32,
+    ) -> Result<(), DbErr> {
+        let location = Entity::find()
+            .filter(Column::Doc.eq(doc))
+            .filter(Column::IsVoided.eq(false))
+            .order_by_desc(Column::Ts)
+            .one(db)
+            .await?
+            .map_or(0, |latest| latest.location);
+        crate::residents::Entity::update_many()
+            .col_expr(
+                crate::residents::Column::CurrentLocation,
+                Expr::value(location),
+            )
+            .filter(crate::residents::Column::Doc.eq(doc))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -81,6 +411,22 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Residents,
+    #[sea_orm(
+        belongs_to = "super::devices::Entity",
+        from = "Column::DeviceId",
+        to = "super::devices::Column::Id",
+        on_update = "NoAction",
+        on_delete = "SetNull"
+    )]
+    Devices,
+    #[sea_orm(has_many = "super::timestamp_adjustments::Entity")]
+    Adjustments,
+}
+
+impl Related<super::devices::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Devices.def()
+    }
 }
 
 impl Related<super::locations::Entity> for Entity {
@@ -95,4 +441,10 @@ impl Related<super::residents::Entity> for Entity {
     }
 }
 
+impl Related<super::timestamp_adjustments::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Adjustments.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
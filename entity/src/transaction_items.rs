@@ -1,7 +1,10 @@
 use crate::prelude::OrmSerializable;
-use sea_orm::entity::prelude::*;
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime};
+use sea_orm::{entity::prelude::*, QuerySelect};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 impl OrmSerializable for Model {}
+impl OrmSerializable for GrossMargin {}
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq, Serialize, Deserialize)]
 #[sea_orm(table_name = "transaction_items")]
@@ -11,6 +14,150 @@ pub struct Model {
     pub transaction_id: i32,
     pub item_id: i32,
     pub quantity: i32,
+    /// unit price in cents when sold, refunds give back this rather than the current price
+    pub price: i32,
+    /// average cost in cents of a unit when sold, refunds restock it at this cost
+    pub unit_cost: i32,
+}
+
+/// What sold, and what it cost, over a day, week or month in cents. Voids and refunds count against it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Margin {
+    pub quantity: i64,
+    pub revenue: i64,
+    pub cost: i64,
+    pub margin: i64,
+    /// margin as a percentage of revenue
+    pub margin_percent: Option<f64>,
+}
+
+impl Margin {
+    fn add(&mut self, quantity: i64, price: i32, unit_cost: i32) {
+        self.quantity += quantity;
+        self.revenue += quantity * price as i64;
+        self.cost += quantity * unit_cost as i64;
+        self.margin = self.revenue - self.cost;
+        self.margin_percent = (self.revenue != 0)
+            .then(|| (self.margin as f64 / self.revenue as f64 * 10000.0).round() / 100.0);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ItemMargin {
+    pub item_id: i32,
+    pub upc: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub margin: Margin,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PeriodMargin {
+    /// first day of the period
+    pub start: NaiveDate,
+    #[serde(flatten)]
+    pub margin: Margin,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GrossMargin {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// day, week or month
+    pub interval: String,
+    pub items: Vec<ItemMargin>,
+    pub periods: Vec<PeriodMargin>,
+    #[serde(flatten)]
+    pub total: Margin,
+}
+
+/// The first day of the day, week (from monday) or month `date` falls in
+fn period_start(date: NaiveDate, interval: &str) -> NaiveDate {
+    match interval {
+        "week" => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+        "month" => date.with_day(1).unwrap_or(date),
+        _ => date,
+    }
+}
+
+/// Revenue against the cost of what was sold from `start` to `end` inclusive, per item and per `interval`
+pub async fn gross_margin(
+    db: &DatabaseConnection,
+    start: NaiveDate,
+    end: NaiveDate,
+    interval: &str,
+) -> Result<GrossMargin, DbErr> {
+    let transactions: Vec<(i32, String, DateTime)> = super::transactions::Entity::find()
+        .select_only()
+        .column(super::transactions::Column::Id)
+        .column(super::transactions::Column::Kind)
+        .column(super::transactions::Column::Timestamp)
+        .filter(super::transactions::Column::Kind.is_in(["purchase", "void", "refund"]))
+        .filter(super::transactions::Column::Timestamp.gte(start.and_time(NaiveTime::MIN)))
+        .filter(
+            super::transactions::Column::Timestamp
+                .lt((end + Duration::days(1)).and_time(NaiveTime::MIN)),
+        )
+        .into_tuple()
+        .all(db)
+        .await?;
+    // sales count up and what was given back counts down, in the period it was given back
+    let sold: HashMap<i32, (i64, NaiveDate)> = transactions
+        .into_iter()
+        .map(|(id, kind, timestamp)| {
+            let sign = if kind == "purchase" { 1 } else { -1 };
+            (id, (sign, period_start(timestamp.date(), interval)))
+        })
+        .collect();
+    let lines = Entity::find()
+        .filter(Column::TransactionId.is_in(sold.keys().copied()))
+        .all(db)
+        .await?;
+    let items: HashMap<i32, super::items::Model> = super::items::Entity::find()
+        .filter(super::items::Column::Id.is_in(lines.iter().map(|line| line.item_id)))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|item| (item.id, item))
+        .collect();
+    let mut by_item: BTreeMap<i32, Margin> = BTreeMap::new();
+    let mut by_period: BTreeMap<NaiveDate, Margin> = BTreeMap::new();
+    let mut total = Margin::default();
+    for line in &lines {
+        let (sign, period) = sold[&line.transaction_id];
+        let quantity = sign * line.quantity as i64;
+        by_item
+            .entry(line.item_id)
+            .or_default()
+            .add(quantity, line.price, line.unit_cost);
+        by_period
+            .entry(period)
+            .or_default()
+            .add(quantity, line.price, line.unit_cost);
+        total.add(quantity, line.price, line.unit_cost);
+    }
+    Ok(GrossMargin {
+        start,
+        end,
+        interval: interval.to_string(),
+        items: by_item
+            .into_iter()
+            .map(|(item_id, margin)| {
+                let item = items.get(&item_id).cloned().unwrap_or_default();
+                ItemMargin {
+                    item_id,
+                    upc: item.upc,
+                    name: item.name,
+                    margin,
+                }
+            })
+            .collect(),
+        periods: by_period
+            .into_iter()
+            .map(|(start, margin)| PeriodMargin { start, margin })
+            .collect(),
+        total,
+    })
 }
 
 #[derive(Debug, EnumIter, Eq, PartialEq, Clone, Serialize, DeriveRelation, Deserialize)]
@@ -1,8 +1,14 @@
 use std::ops::Mul;
 
+use crate::ledger_postings::{self, Entry};
 use crate::prelude::OrmSerializable;
-use sea_orm::{entity::prelude::*, IntoActiveModel, Set};
+use crate::purchase_limits;
+use sea_orm::{
+    entity::prelude::*, sea_query::Expr, DatabaseTransaction, QueryOrder, QuerySelect, Set,
+    TransactionTrait,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 impl OrmSerializable for Model {}
 #[derive(
@@ -17,6 +23,8 @@ pub struct Model {
     pub kind: String,
     pub amount: i32,
     pub timestamp: DateTime,
+    /// the purchase a void or refund gives back
+    pub original_id: Option<i32>,
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -31,6 +39,43 @@ pub struct PostTransaction {
     pub kind: String,
     pub amount: f64,
     pub items: Option<Vec<PostItem>>,
+    /// the receiving account of a transfer
+    pub to_account: Option<i32>,
+    /// what to do with purchase lines there isn't enough stock for, `STOCK_POLICY` when not given
+    pub stock_policy: Option<StockPolicy>,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StockPolicy {
+    /// refuse the whole purchase
+    Reject,
+    /// sell what's left and charge only for that
+    Partial,
+}
+
+impl Default for StockPolicy {
+    fn default() -> Self {
+        match std::env::var("STOCK_POLICY").as_deref() {
+            Ok("partial") => Self::Partial,
+            _ => Self::Reject,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct LineResult {
+    pub upc: String,
+    pub requested: i32,
+    pub filled: i32,
+    pub price: i32,
+    /// filled, partial or out_of_stock
+    pub status: String,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct PostRefund {
+    pub items: Vec<PostItem>,
 }
 
 #[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
@@ -40,6 +85,9 @@ pub struct TransactionResult {
     pub kind: String,
     pub amount: i32,
     pub balance: i32,
+    /// what each line of a purchase was filled with
+    #[serde(default)]
+    pub lines: Vec<LineResult>,
 }
 
 impl TransactionResult {
@@ -48,8 +96,9 @@ impl TransactionResult {
             doc: transaction.doc,
             account_id: id,
             kind: transaction.kind.clone(),
-            amount: transaction.amount.mul(100.0) as i32,
+            amount: transaction.amount.mul(100.0).round() as i32,
             balance: 0,
+            lines: vec![],
         }
     }
 }
@@ -57,81 +106,327 @@ impl TransactionResult {
 impl OrmSerializable for TransactionResult {}
 
 impl PostTransaction {
+    /// Credits, charges a fee to, transfers from or purchases items with the account in a single database
+    /// transaction, writing its double-entry ledger postings alongside. The account and the purchased
+    /// items are locked with `SELECT ... FOR UPDATE` until it commits, so concurrent purchases for the
+    /// same resident are applied one after the other, and nothing is written if any step fails.
     #[rustfmt::skip]
     pub async fn process_transaction(&self, db: &DatabaseConnection, id: i32) -> Result<TransactionResult, Box<dyn std::error::Error>> {
-        let mut result = TransactionResult::init(self, id);
+        let txn = db.begin().await?;
+        let result = self.apply(&txn, id).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
 
-        let account = crate::accounts::Entity::find()
-            .filter(crate::accounts::Column::Id.eq(result.account_id))
-            .one(db)
+    #[rustfmt::skip]
+    async fn apply(&self, txn: &DatabaseTransaction, id: i32) -> Result<TransactionResult, Box<dyn std::error::Error>> {
+        let mut result = TransactionResult::init(self, id);
+        let to_account = match (self.kind.as_str(), self.to_account) {
+            ("transfer", Some(to)) if to != id => Some(to),
+            ("transfer", _) => return Err("A transfer needs a to_account other than the source account".into()),
+            _ => None,
+        };
+        // locked in id order so two transfers between the same accounts can't deadlock
+        let accounts = crate::accounts::Entity::find()
+            .filter(crate::accounts::Column::Id.is_in([Some(id), to_account].into_iter().flatten()))
+            .order_by_asc(crate::accounts::Column::Id)
+            .lock_exclusive()
+            .all(txn)
             .await?;
-        if account.is_none() {
+        let Some(account) = accounts.iter().find(|account| account.id == id) else {
             return Err("Account not found".into());
+        };
+        if matches!(self.kind.as_str(), "credit" | "fee" | "transfer") && result.amount <= 0 {
+            return Err("Amount must be positive".into());
         }
-        let account = account.unwrap();
-        match &self.kind.as_str() {
-             &"credit" => {
-                let new_balance = account.balance + self.amount.mul(100.0) as i32;
-                let mut active_model = account.into_active_model();
-                active_model.balance = Set(new_balance);
-                let _ = active_model.save(db).await?;
-                result.balance = new_balance;
-                Ok(result)
+        let mut lines = vec![];
+        let entries = match self.kind.as_str() {
+            "credit" => vec![Entry::resident(id, result.amount), Entry::house(ledger_postings::DEPOSITS, -result.amount)],
+            "fee" => vec![Entry::resident(id, -result.amount), Entry::house(ledger_postings::FEES, result.amount)],
+            "transfer" => {
+                let Some(to) = accounts.iter().find(|account| account.id != id) else {
+                    return Err("Account not found".into());
+                };
+                vec![Entry::resident(id, -result.amount), Entry::resident(to.id, result.amount)]
             }
-            _ => {
-                if account.balance == 0 || account.balance.mul(100) < self.amount.mul(100.0) as i32
-                {
-                    return Err("Insufficient funds".into());
+            "purchase" => {
+                let post_items = self.items.clone().unwrap_or_default();
+                if post_items.is_empty() {
+                    return Err("A purchase needs at least one item".into());
                 }
-                let mut items_vec = vec![];
+                // locked in id order so two purchases of the same items can't deadlock
+                let items: HashMap<String, crate::items::Model> = crate::items::Entity::find()
+                    .filter(crate::items::Column::Upc.is_in(post_items.iter().map(|item| item.upc.clone())))
+                    .filter(crate::items::Column::IsDeleted.eq(false))
+                    .order_by_asc(crate::items::Column::Id)
+                    .lock_exclusive()
+                    .all(txn)
+                    .await?
+                    .into_iter()
+                    .map(|item| (item.upc.clone(), item))
+                    .collect();
+                let policy = self.stock_policy.unwrap_or_default();
+                // stock left of each item as the lines take from it, an item can be on several lines
+                let mut stock: HashMap<i32, i32> = items.values().map(|item| (item.id, item.quantity.max(0))).collect();
                 let mut total = 0;
-                for post_item in self.items.clone().unwrap() {
-                    if let Ok(Some(mut item)) = crate::items::Entity::find()
-                        .filter(crate::items::Column::Upc.eq(post_item.upc))
-                        .one(db)
-                        .await
-                    {
-                        total += (item.price) * (post_item.quantity);
-                        item.quantity -= post_item.quantity;
-                        items_vec.push(item.clone());
+                for post_item in post_items {
+                    let Some(item) = items.get(&post_item.upc) else {
+                        return Err(format!("Item {} not found", post_item.upc).into());
+                    };
+                    if post_item.quantity <= 0 {
+                        return Err(format!("Invalid quantity for item {}", post_item.upc).into());
+                    }
+                    let available = stock.entry(item.id).or_default();
+                    if *available < post_item.quantity && policy == StockPolicy::Reject {
+                        return Err(format!("Only {} of item {} in stock", available, post_item.upc).into());
+                    }
+                    let filled = post_item.quantity.min(*available);
+                    *available -= filled;
+                    let status = match filled {
+                        0 => "out_of_stock",
+                        filled if filled < post_item.quantity => "partial",
+                        _ => "filled",
+                    };
+                    result.lines.push(LineResult {
+                        upc: post_item.upc,
+                        requested: post_item.quantity,
+                        filled,
+                        price: item.price,
+                        status: status.to_string(),
+                    });
+                    if filled > 0 {
+                        total += item.price * filled;
+                        lines.push((item.id, filled, item.price, item.unit_cost));
                     }
                 }
-                result.amount = total;
-                if account.balance < result.amount {
-                    return Err("Insufficient funds".into());
+                if lines.is_empty() {
+                    return Err("None of the items are in stock".into());
                 }
-                let active_model: ActiveModel = ActiveModel {
-                    kind: Set(result.kind.clone()),
-                    amount: Set(result.amount),
-                    account_id: Set(result.account_id),
-                    ..Default::default()
-                };
-                let transaction = active_model.save(db).await?;
-                let transaction_id = transaction.id.clone().unwrap();
-                for item in items_vec {
-                    let item_id = item.id;
-                    let transaction_item_model = crate::transaction_items::ActiveModel {
-                        item_id: Set(item_id),
-                        transaction_id: Set(transaction_id),
-                        ..Default::default()
-                    };
-                    let _ = transaction_item_model.save(db).await?;
-                    let event = crate::inventory_event::ActiveModel {
-                        item_id: Set(item_id),
-                        quantity: Set(item.quantity),
-                        is_add: Set(false),
-                        ..Default::default()
-                    };
-                    let _ = event.save(db).await?;
+                // the account lock keeps the resident's spending steady while it's checked
+                if let Some(resident) = crate::residents::Entity::find().filter(crate::residents::Column::Doc.eq(account.doc)).one(txn).await? {
+                    let bought: Vec<(i32, i32)> = lines.iter().map(|(item_id, quantity, _, _)| (*item_id, *quantity)).collect();
+                    if let Some(reason) = purchase_limits::violation(txn, &resident, id, &bought, total).await? {
+                        return Err(reason.into());
+                    }
                 }
-                let new_balance = account.balance - self.amount.mul(100.0) as i32;
-                let mut active_model = account.into_active_model();
-                active_model.balance = Set(new_balance);
-                let _ = active_model.save(db).await?;
-                result.balance = new_balance;
-                Ok(result)
+                result.amount = total;
+                vec![Entry::resident(id, -total), Entry::house(ledger_postings::SALES, total)]
+            }
+            // they have to point back at the purchase they give back, which only `void` and `refund` do
+            "void" | "refund" => return Err("Voids and refunds are made from the purchase they reverse".into()),
+            kind => return Err(format!("Unknown transaction kind: {kind}").into()),
+        };
+        let change: i32 = entries.iter().filter(|entry| entry.account_id == Some(id)).map(|entry| entry.amount).sum();
+        if account.balance + change < 0 {
+            return Err("Insufficient funds".into());
+        }
+        let transaction = ActiveModel {
+            doc: Set(account.doc),
+            account_id: Set(account.id),
+            kind: Set(result.kind.clone()),
+            amount: Set(result.amount),
+            timestamp: Set(chrono::Local::now().naive_local()),
+            original_id: Set(None),
+            ..Default::default()
+        }
+        .insert(txn)
+        .await?;
+        ledger_postings::post(txn, Some(transaction.id), &entries).await?;
+        for (item_id, quantity, price, unit_cost) in lines {
+            crate::transaction_items::ActiveModel {
+                transaction_id: Set(transaction.id),
+                item_id: Set(item_id),
+                quantity: Set(quantity),
+                price: Set(price),
+                unit_cost: Set(unit_cost),
+                ..Default::default()
+            }
+            .insert(txn)
+            .await?;
+            crate::inventory_event::ActiveModel {
+                item_id: Set(item_id),
+                quantity: Set(quantity),
+                is_add: Set(false),
+                purchase_order_id: Set(None),
+                unit_cost: Set(unit_cost),
+                created_at: Set(transaction.timestamp),
+                ..Default::default()
+            }
+            .insert(txn)
+            .await?;
+            crate::items::Entity::update_many()
+                .col_expr(crate::items::Column::Quantity, Expr::col(crate::items::Column::Quantity).sub(quantity))
+                .filter(crate::items::Column::Id.eq(item_id))
+                .exec(txn)
+                .await?;
+        }
+        // the stored balances follow the resident postings
+        for entry in entries.iter().filter(|entry| entry.account_id.is_some()) {
+            crate::accounts::Entity::update_many()
+                .col_expr(crate::accounts::Column::Balance, Expr::col(crate::accounts::Column::Balance).add(entry.amount))
+                .filter(crate::accounts::Column::Id.eq(entry.account_id))
+                .exec(txn)
+                .await?;
+        }
+        result.doc = account.doc;
+        result.balance = account.balance + change;
+        Ok(result)
+    }
+}
+
+impl Model {
+    /// Gives back a purchase made today in full, as long as nothing of it was refunded yet
+    #[rustfmt::skip]
+    pub async fn void(db: &DatabaseConnection, id: i32) -> Result<TransactionResult, Box<dyn std::error::Error>> {
+        let txn = db.begin().await?;
+        let original = Self::reversible(&txn, id).await?;
+        if original.timestamp.date() != chrono::Local::now().date_naive() {
+            return Err("Only purchases made today can be voided, refund it instead".into());
+        }
+        if !original.reversals(&txn).await?.is_empty() {
+            return Err("This purchase was already voided or refunded".into());
+        }
+        let lines = crate::transaction_items::Entity::find()
+            .filter(crate::transaction_items::Column::TransactionId.eq(id))
+            .all(&txn)
+            .await?
+            .into_iter()
+            .map(|line| (line.item_id, line.quantity, line.price, line.unit_cost))
+            .collect();
+        let result = original.reverse(&txn, "void", original.amount, lines).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
+
+    /// Gives back some of the items of a purchase at the price they were sold for, never more of an
+    /// item than was bought and not refunded already
+    #[rustfmt::skip]
+    pub async fn refund(db: &DatabaseConnection, id: i32, refund: &PostRefund) -> Result<TransactionResult, Box<dyn std::error::Error>> {
+        let txn = db.begin().await?;
+        let original = Self::reversible(&txn, id).await?;
+        let reversals = original.reversals(&txn).await?;
+        if reversals.iter().any(|reversal| reversal.kind == "void") {
+            return Err("This purchase was already voided".into());
+        }
+        let sold = crate::transaction_items::Entity::find()
+            .filter(crate::transaction_items::Column::TransactionId.eq(id))
+            .all(&txn)
+            .await?;
+        let upcs: HashMap<i32, String> = crate::items::Entity::find()
+            .filter(crate::items::Column::Id.is_in(sold.iter().map(|line| line.item_id)))
+            .all(&txn)
+            .await?
+            .into_iter()
+            .map(|item| (item.id, item.upc))
+            .collect();
+        let mut returned: HashMap<i32, i32> = HashMap::new();
+        for line in crate::transaction_items::Entity::find()
+            .filter(crate::transaction_items::Column::TransactionId.is_in(reversals.iter().map(|reversal| reversal.id)))
+            .all(&txn)
+            .await?
+        {
+            *returned.entry(line.item_id).or_default() += line.quantity;
+        }
+        let mut lines: Vec<(i32, i32, i32, i32)> = vec![];
+        for post_item in &refund.items {
+            let Some(line) = sold.iter().find(|line| upcs.get(&line.item_id) == Some(&post_item.upc)) else {
+                return Err(format!("Item {} wasn't part of this purchase", post_item.upc).into());
+            };
+            if post_item.quantity <= 0 {
+                return Err(format!("Invalid quantity for item {}", post_item.upc).into());
+            }
+            let bought: i32 = sold.iter().filter(|other| other.item_id == line.item_id).map(|other| other.quantity).sum();
+            let refunding = returned.entry(line.item_id).or_default();
+            if *refunding + post_item.quantity > bought {
+                return Err(format!("Only {} of item {} can still be refunded", bought - *refunding, post_item.upc).into());
+            }
+            *refunding += post_item.quantity;
+            lines.push((line.item_id, post_item.quantity, line.price, line.unit_cost));
+        }
+        if lines.is_empty() {
+            return Err("A refund needs at least one item".into());
+        }
+        let amount = lines.iter().map(|(_, quantity, price, _)| quantity * price).sum();
+        let result = original.reverse(&txn, "refund", amount, lines).await?;
+        txn.commit().await?;
+        Ok(result)
+    }
+
+    /// Locks the purchase so two voids or refunds of it are applied one after the other
+    #[rustfmt::skip]
+    async fn reversible(txn: &DatabaseTransaction, id: i32) -> Result<Self, Box<dyn std::error::Error>> {
+        let Some(original) = Entity::find_by_id(id).lock_exclusive().one(txn).await? else {
+            return Err("Transaction not found".into());
+        };
+        if original.kind != "purchase" {
+            return Err("Only purchases can be voided or refunded".into());
+        }
+        Ok(original)
+    }
+
+    async fn reversals(&self, txn: &DatabaseTransaction) -> Result<Vec<Self>, DbErr> {
+        Entity::find()
+            .filter(Column::OriginalId.eq(self.id))
+            .all(txn)
+            .await
+    }
+
+    /// Credits the amount back to the account and restocks the (item, quantity, price, unit cost) lines at what they cost
+    #[rustfmt::skip]
+    async fn reverse(&self, txn: &DatabaseTransaction, kind: &str, amount: i32, lines: Vec<(i32, i32, i32, i32)>) -> Result<TransactionResult, Box<dyn std::error::Error>> {
+        let Some(account) = crate::accounts::Entity::find_by_id(self.account_id).lock_exclusive().one(txn).await? else {
+            return Err("Account not found".into());
+        };
+        let transaction = ActiveModel {
+            doc: Set(account.doc),
+            account_id: Set(account.id),
+            kind: Set(kind.to_string()),
+            amount: Set(amount),
+            timestamp: Set(chrono::Local::now().naive_local()),
+            original_id: Set(Some(self.id)),
+            ..Default::default()
+        }
+        .insert(txn)
+        .await?;
+        let entries = [Entry::resident(account.id, amount), Entry::house(ledger_postings::SALES, -amount)];
+        ledger_postings::post(txn, Some(transaction.id), &entries).await?;
+        for (item_id, quantity, price, unit_cost) in lines {
+            crate::transaction_items::ActiveModel {
+                transaction_id: Set(transaction.id),
+                item_id: Set(item_id),
+                quantity: Set(quantity),
+                price: Set(price),
+                unit_cost: Set(unit_cost),
+                ..Default::default()
             }
+            .insert(txn)
+            .await?;
+            crate::inventory_event::ActiveModel {
+                item_id: Set(item_id),
+                quantity: Set(quantity),
+                is_add: Set(true),
+                purchase_order_id: Set(None),
+                unit_cost: Set(unit_cost),
+                created_at: Set(transaction.timestamp),
+                ..Default::default()
+            }
+            .insert(txn)
+            .await?;
+            crate::items::restock(txn, item_id, quantity, unit_cost).await?;
         }
+        crate::accounts::Entity::update_many()
+            .col_expr(crate::accounts::Column::Balance, Expr::col(crate::accounts::Column::Balance).add(amount))
+            .filter(crate::accounts::Column::Id.eq(account.id))
+            .exec(txn)
+            .await?;
+        Ok(TransactionResult {
+            doc: account.doc,
+            account_id: account.id,
+            kind: kind.to_string(),
+            amount,
+            balance: account.balance + amount,
+            lines: vec![],
+        })
     }
 }
 
@@ -1,7 +1,8 @@
 use pwhash::bcrypt;
-use sea_orm::entity::prelude::*;
+use sea_orm::{entity::prelude::*, QuerySelect, Set, SqlErr, TransactionTrait};
 use serde::{Deserialize, Serialize};
 
+use crate::levels::{self, Permission};
 use crate::prelude::OrmSerializable;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Serialize, Deserialize)]
@@ -12,20 +13,159 @@ pub struct Model {
     pub email: String,
     #[serde(skip_serializing)]
     pub password: String,
+    pub level: i32,
+    /// a disabled user can't log in, but keeps their history
+    pub is_disabled: bool,
+    /// set when an admin resets the password, the user can do nothing else until they change it
+    pub must_change_password: bool,
 }
 
+/// Shortest password accepted, bcrypt only reads the first 72 bytes so that's the longest
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+pub const MAX_PASSWORD_LENGTH: usize = 72;
+
 impl OrmSerializable for Model {}
 
 impl Model {
     pub fn verify_password(&self, password: &str) -> bool {
         bcrypt::verify(password, &self.password)
     }
-    pub fn hash_password(password: &str) -> String {
-        bcrypt::hash(password).unwrap()
+    /// Needs a letter and a digit, and the length to be within bounds
+    pub fn validate_password(password: &str) -> Result<(), String> {
+        if password.len() < MIN_PASSWORD_LENGTH {
+            return Err(format!(
+                "Password must be at least {MIN_PASSWORD_LENGTH} characters"
+            ));
+        }
+        if password.len() > MAX_PASSWORD_LENGTH {
+            return Err(format!(
+                "Password can't be longer than {MAX_PASSWORD_LENGTH} characters"
+            ));
+        }
+        if !password.chars().any(char::is_alphabetic)
+            || !password.chars().any(|c| c.is_ascii_digit())
+        {
+            return Err("Password must contain both letters and numbers".to_string());
+        }
+        Ok(())
+    }
+    /// Refuses a password that doesn't pass `validate_password`
+    pub fn hash_password(password: &str) -> Result<String, String> {
+        Self::validate_password(password)?;
+        bcrypt::hash(password).map_err(|e| e.to_string())
+    }
+    /// Checks a password against a throwaway hash, taking as long as checking a real user's
+    pub fn verify_nobody(password: &str) -> bool {
+        static HASH: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+        bcrypt::verify(
+            password,
+            HASH.get_or_init(|| bcrypt::hash("nobody").unwrap_or_default()),
+        )
+    }
+    /// Creates the first admin of a fresh install, refused once any user exists
+    pub async fn bootstrap_admin(
+        db: &DatabaseConnection,
+        email: &str,
+        password: &str,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let password = Self::hash_password(password)?;
+        let txn = db.begin().await?;
+        // two bootstraps at once would otherwise both find the table empty
+        txn.execute_unprepared("LOCK TABLE users IN SHARE ROW EXCLUSIVE MODE")
+            .await?;
+        if Entity::find().count(&txn).await? > 0 {
+            return Err("Users already exist, log in as an admin to add more".into());
+        }
+        let Some(level) = levels::Entity::find()
+            .filter(levels::Column::Permission.eq(i32::from(Permission::Admin)))
+            .one(&txn)
+            .await?
+        else {
+            return Err("The admin level is missing".into());
+        };
+        let user = ActiveModel {
+            email: Set(email.to_string()),
+            password: Set(password),
+            level: Set(level.id),
+            is_disabled: Set(false),
+            must_change_password: Set(false),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        txn.commit().await?;
+        Ok(user)
+    }
+    pub async fn find_by_email(
+        db: &DatabaseConnection,
+        email: &str,
+    ) -> Result<Option<Self>, DbErr> {
+        Entity::find().filter(Column::Email.eq(email)).one(db).await
+    }
+    /// Whether anything is attributed to the user that deleting them would lose
+    pub async fn has_history<C: ConnectionTrait>(&self, db: &C) -> Result<bool, DbErr> {
+        use crate::{
+            auth_events, headcounts, purchase_orders, stock_counts, timestamp_adjustments,
+        };
+        Ok(purchase_orders::Entity::find()
+            .filter(purchase_orders::Column::CreatedBy.eq(self.id))
+            .count(db)
+            .await?
+            + stock_counts::Entity::find()
+                .filter(stock_counts::Column::StartedBy.eq(self.id))
+                .count(db)
+                .await?
+            + headcounts::Entity::find()
+                .filter(headcounts::Column::StartedBy.eq(self.id))
+                .count(db)
+                .await?
+            + timestamp_adjustments::Entity::find()
+                .filter(timestamp_adjustments::Column::UserId.eq(self.id))
+                .count(db)
+                .await?
+            + auth_events::Entity::find()
+                .filter(auth_events::Column::UserId.eq(self.id))
+                .count(db)
+                .await?
+            > 0)
+    }
+    /// Deletes a user with no history, returning false for one that has to be disabled instead.
+    /// The user is locked while checking so nothing can be attributed to them before they're gone.
+    pub async fn delete_without_history(db: &DatabaseConnection, id: i32) -> Result<bool, DbErr> {
+        let txn = db.begin().await?;
+        let Some(user) = Entity::find_by_id(id).lock_exclusive().one(&txn).await? else {
+            return Err(DbErr::RecordNotFound("User not found".to_string()));
+        };
+        if user.has_history(&txn).await? {
+            return Ok(false);
+        }
+        match Entity::delete_by_id(id).exec(&txn).await {
+            Ok(_) => {
+                txn.commit().await?;
+                Ok(true)
+            }
+            Err(e) if matches!(e.sql_err(), Some(SqlErr::ForeignKeyConstraintViolation(_))) => {
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
     }
 }
 
 #[derive(DeriveRelation, Copy, Clone, Debug, EnumIter)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::levels::Entity",
+        from = "Column::Level",
+        to = "super::levels::Column::Id"
+    )]
+    Levels,
+}
+
+impl Related<super::levels::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Levels.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
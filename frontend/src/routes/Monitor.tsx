@@ -14,7 +14,9 @@ function Monitor() {
   const [allResidents, setAllResidents] = createSignal<SResident[]>([]);
   const [currentLocation, setCurrentLocation] = createSignal<SLocation | null>(null);
 
+  let events: EventSource | undefined;
   let intervalId: number | undefined;
+  let retryId: number | undefined;
 
   const fetchResidentsForLocation = async (locationId: number) => {
     try {
@@ -44,9 +46,57 @@ function Monitor() {
     setResidents(getResidentsByLocation(currentLocation()!.id));
   };
 
+  const startPolling = (locationId: number) => {
+    if (intervalId === undefined) {
+      intervalId = setInterval(() => fetchResidentsForLocation(locationId), 5000);
+    }
+  };
+
+  const stopPolling = () => {
+    clearInterval(intervalId);
+    intervalId = undefined;
+  };
+
+  const unsubscribe = () => {
+    events?.close();
+    events = undefined;
+    clearTimeout(retryId);
+    stopPolling();
+  };
+
+  // the server pushes every scan into or out of the location, so we only re-fetch when something changed.
+  // while the stream is down (or the browser has no EventSource) we fall back to polling every 5 seconds
+  const subscribeToLocation = (locationId: number) => {
+    unsubscribe();
+    if (typeof EventSource === "undefined") {
+      startPolling(locationId);
+      return;
+    }
+    events = new EventSource(`${API.fullUrl}locations/${locationId}/events`, { withCredentials: true });
+    const refresh = () => fetchResidentsForLocation(locationId);
+    events.addEventListener("scan", refresh);
+    events.addEventListener("resync", refresh);
+    events.onopen = () => {
+      stopPolling();
+      refresh();
+    };
+    events.onerror = () => {
+      startPolling(locationId);
+      // the browser retries on its own unless it gave up and closed the stream
+      if (events?.readyState === EventSource.CLOSED) {
+        retryId = setTimeout(() => {
+          if (currentLocation()?.id === locationId) {
+            subscribeToLocation(locationId);
+          }
+        }, 30000);
+      }
+    };
+  };
+
   const handleLocationChange = (locationId: number) => {
     setCurrentLocation(allLocations().find(location => location.id === locationId)!);
     setResidents(getResidentsByLocation(locationId));
+    subscribeToLocation(locationId);
     setShowModal(false);
     setShowTable(true);
   };
@@ -70,15 +120,10 @@ function Monitor() {
 
   onMount(() => {
     checkLocalStorage();
-    intervalId = setInterval(() => {
-      if (currentLocation()) {
-        fetchResidentsForLocation(currentLocation()!.id);
-      }
-    }, 5000);
   });
 
   onCleanup(() => {
-    clearInterval(intervalId);
+    unsubscribe();
   });
 
   return (
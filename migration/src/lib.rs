@@ -15,6 +15,28 @@ mod m20240227_173940_add_inventory;
 mod m20240305_024157_seed_accounts_transactions;
 mod m20240305_030602_seed_transactions;
 mod m20240305_132005_seed_transaction_items;
+mod m20261018_101500_user_levels;
+mod m20261018_113000_devices;
+mod m20261018_121500_timestamp_idempotency;
+mod m20261018_130000_work_locations;
+mod m20261018_140000_timestamp_adjustments;
+mod m20261018_150000_headcounts;
+mod m20261018_160000_location_limits;
+mod m20261018_170000_movement_rules;
+mod m20261018_180000_callouts;
+mod m20261018_180500_transaction_integrity;
+mod m20261018_190000_ledger_postings;
+mod m20261018_200000_refunds;
+mod m20261018_210000_purchase_limits;
+mod m20261018_220000_purchase_orders;
+mod m20261018_230000_inventory_costs;
+mod m20261018_233000_stock_counts;
+mod m20261018_234500_reorder_points;
+mod m20261018_235000_auth_sessions;
+mod m20261018_235300_auth_events;
+mod m20261018_235600_user_management;
+mod m20261018_235800_overdrawn_accounts;
+mod m20261018_235900_rejected_scan_idempotency;
 
 pub struct Migrator;
 
@@ -37,6 +59,28 @@ impl MigratorTrait for Migrator {
             Box::new(m20240305_024157_seed_accounts_transactions::Migration),
             Box::new(m20240305_030602_seed_transactions::Migration),
             Box::new(m20240305_132005_seed_transaction_items::Migration),
+            Box::new(m20261018_101500_user_levels::Migration),
+            Box::new(m20261018_113000_devices::Migration),
+            Box::new(m20261018_121500_timestamp_idempotency::Migration),
+            Box::new(m20261018_130000_work_locations::Migration),
+            Box::new(m20261018_140000_timestamp_adjustments::Migration),
+            Box::new(m20261018_150000_headcounts::Migration),
+            Box::new(m20261018_160000_location_limits::Migration),
+            Box::new(m20261018_170000_movement_rules::Migration),
+            Box::new(m20261018_180000_callouts::Migration),
+            Box::new(m20261018_180500_transaction_integrity::Migration),
+            Box::new(m20261018_190000_ledger_postings::Migration),
+            Box::new(m20261018_200000_refunds::Migration),
+            Box::new(m20261018_210000_purchase_limits::Migration),
+            Box::new(m20261018_220000_purchase_orders::Migration),
+            Box::new(m20261018_230000_inventory_costs::Migration),
+            Box::new(m20261018_233000_stock_counts::Migration),
+            Box::new(m20261018_234500_reorder_points::Migration),
+            Box::new(m20261018_235000_auth_sessions::Migration),
+            Box::new(m20261018_235300_auth_events::Migration),
+            Box::new(m20261018_235600_user_management::Migration),
+            Box::new(m20261018_235800_overdrawn_accounts::Migration),
+            Box::new(m20261018_235900_rejected_scan_idempotency::Migration),
         ]
     }
 }
@@ -21,6 +21,7 @@ impl MigrationTrait for Migration {
                 id: Set(loc.id.to_owned()),
                 name: Set(loc.name.to_owned()),
                 level: Set(2),
+                ..Default::default()
             })
             .collect();
 
@@ -1,6 +1,6 @@
 use sea_orm_migration::{
     prelude::*,
-    sea_orm::{EntityTrait, IntoActiveModel},
+    sea_orm::{EntityTrait, Set},
 };
 
 #[derive(DeriveMigrationName)]
@@ -26,15 +26,18 @@ impl MigrationTrait for Migration {
                     .to_owned(),
             )
             .await?;
+        // production starts without users, the first admin is made with `scan_mvcf create-admin`
+        if std::env::var("APP_ENV").is_ok_and(|env| env == "production") {
+            return Ok(());
+        }
         let db = manager.get_connection();
-        let user = entity::users::Model {
-            id: Default::default(),
-            email: "admin".to_owned(),
-            password: entity::users::Model::hash_password("admin"),
+        let user = entity::users::ActiveModel {
+            email: Set("admin".to_owned()),
+            // development login only, hashed directly as it predates the password rules
+            password: Set(pwhash::bcrypt::hash("admin").unwrap()),
+            ..Default::default()
         };
-        entity::users::Entity::insert(user.into_active_model())
-            .exec(db)
-            .await?;
+        entity::users::Entity::insert(user).exec(db).await?;
         Ok(())
     }
 
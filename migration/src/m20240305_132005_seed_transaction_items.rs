@@ -1,6 +1,6 @@
 use sea_orm_migration::{
     prelude::*,
-    sea_orm::{EntityTrait, Set},
+    sea_orm::{EntityTrait, QuerySelect, Set},
 };
 
 #[derive(DeriveMigrationName)]
@@ -10,7 +10,13 @@ pub struct Migration;
 impl MigrationTrait for Migration {
     async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
         let db = manager.get_connection();
-        let transactions = entity::transactions::Entity::find().all(db).await?;
+        // only the ids, later migrations add columns the table doesn't have yet
+        let transactions: Vec<i32> = entity::transactions::Entity::find()
+            .select_only()
+            .column(entity::transactions::Column::Id)
+            .into_tuple()
+            .all(db)
+            .await?;
         let items: Vec<entity::items::Model> =
             serde_json::from_str(include_str!("../../seed_data/transaction_items.json"))
                 .map_err(|_| DbErr::Custom("Serde".to_string()))
@@ -33,7 +39,7 @@ impl MigrationTrait for Migration {
                     counter += 1;
                 }
                 entity::transaction_items::ActiveModel {
-                    transaction_id: Set(trans.id.to_owned()),
+                    transaction_id: Set(*trans),
                     item_id: Set(counter),
                     quantity: Set(counter * 2),
                     ..Default::default()
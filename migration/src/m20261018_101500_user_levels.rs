@@ -0,0 +1,83 @@
+use entity::levels::{self, Permission};
+use sea_orm_migration::{
+    prelude::*,
+    sea_orm::{ActiveModelTrait, EntityTrait, Set},
+};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        let mut officer = 0;
+        let mut admin = 0;
+        for permission in Permission::ALL {
+            let level = levels::ActiveModel {
+                permission: Set(permission.into()),
+                text: Set(permission.text().to_owned()),
+                ..Default::default()
+            }
+            .insert(db)
+            .await?;
+            match permission {
+                Permission::Officer => officer = level.id,
+                Permission::Admin => admin = level.id,
+                _ => {}
+            }
+        }
+        // new users get the lowest level unless one is given
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(
+                        ColumnDef::new(Users::Level)
+                            .integer()
+                            .not_null()
+                            .default(officer),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_users_level")
+                    .from(Users::Table, Users::Level)
+                    .to(levels::Entity, levels::Column::Id)
+                    .on_delete(ForeignKeyAction::NoAction)
+                    .to_owned(),
+            )
+            .await?;
+        // every existing user had full access before levels were enforced
+        entity::users::Entity::update_many()
+            .col_expr(entity::users::Column::Level, Expr::value(admin))
+            .exec(db)
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_foreign_key(Alias::new("fk_users_level"))
+                    .drop_column(Users::Level)
+                    .to_owned(),
+            )
+            .await?;
+        levels::Entity::delete_many()
+            .exec(manager.get_connection())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    Level,
+}
@@ -0,0 +1,91 @@
+use entity::{devices, locations, timestamps};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(devices::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(devices::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(devices::Column::Name).string().not_null())
+                    .col(
+                        ColumnDef::new(devices::Column::Location)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(devices::Column::ApiKey)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(devices::Column::IsRevoked)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_devices_location")
+                    .from(devices::Entity, devices::Column::Location)
+                    .to(locations::Entity, locations::Column::Id)
+                    .on_delete(ForeignKeyAction::NoAction)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(timestamps::Entity)
+                    .add_column(
+                        ColumnDef::new(timestamps::Column::DeviceId)
+                            .integer()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_timestamps_device")
+                    .from(timestamps::Entity, timestamps::Column::DeviceId)
+                    .to(devices::Entity, devices::Column::Id)
+                    .on_delete(ForeignKeyAction::SetNull)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(timestamps::Entity)
+                    .drop_foreign_key(Alias::new("fk_timestamps_device"))
+                    .drop_column(timestamps::Column::DeviceId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(devices::Entity).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,35 @@
+use entity::timestamps;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(timestamps::Entity)
+                    .add_column(
+                        ColumnDef::new(timestamps::Column::IdempotencyKey)
+                            .string()
+                            .null()
+                            .unique_key(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(timestamps::Entity)
+                    .drop_column(timestamps::Column::IdempotencyKey)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,35 @@
+use entity::locations;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(locations::Entity)
+                    .add_column(
+                        ColumnDef::new(locations::Column::IsWork)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(locations::Entity)
+                    .drop_column(locations::Column::IsWork)
+                    .to_owned(),
+            )
+            .await
+    }
+}
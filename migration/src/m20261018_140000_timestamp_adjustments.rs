@@ -0,0 +1,113 @@
+use entity::{timestamp_adjustments, timestamps, users};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(timestamps::Entity)
+                    .add_column(
+                        ColumnDef::new(timestamps::Column::IsVoided)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(timestamp_adjustments::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(timestamp_adjustments::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(timestamp_adjustments::Column::TimestampId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(timestamp_adjustments::Column::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(timestamp_adjustments::Column::Action)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(timestamp_adjustments::Column::Reason)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(timestamp_adjustments::Column::OldLocation).integer())
+                    .col(ColumnDef::new(timestamp_adjustments::Column::OldTs).timestamp())
+                    .col(ColumnDef::new(timestamp_adjustments::Column::NewLocation).integer())
+                    .col(ColumnDef::new(timestamp_adjustments::Column::NewTs).timestamp())
+                    .col(
+                        ColumnDef::new(timestamp_adjustments::Column::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_timestamp_adjustments_timestamp")
+                    .from(
+                        timestamp_adjustments::Entity,
+                        timestamp_adjustments::Column::TimestampId,
+                    )
+                    .to(timestamps::Entity, timestamps::Column::Id)
+                    .on_delete(ForeignKeyAction::Cascade)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_timestamp_adjustments_user")
+                    .from(
+                        timestamp_adjustments::Entity,
+                        timestamp_adjustments::Column::UserId,
+                    )
+                    .to(users::Entity, users::Column::Id)
+                    .on_delete(ForeignKeyAction::NoAction)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(timestamp_adjustments::Entity)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(timestamps::Entity)
+                    .drop_column(timestamps::Column::IsVoided)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,106 @@
+use entity::{headcount_entries, headcounts, users};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(headcounts::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(headcounts::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(headcounts::Column::Location).integer())
+                    .col(
+                        ColumnDef::new(headcounts::Column::StartedBy)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(headcounts::Column::StartedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(headcounts::Column::ClosedAt).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_headcounts_user")
+                            .from(headcounts::Entity, headcounts::Column::StartedBy)
+                            .to(users::Entity, users::Column::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(headcount_entries::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(headcount_entries::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(headcount_entries::Column::HeadcountId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(headcount_entries::Column::Doc)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(headcount_entries::Column::Kind)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(headcount_entries::Column::Location)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(headcount_entries::Column::ConfirmedAt).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_headcount_entries_headcount")
+                            .from(
+                                headcount_entries::Entity,
+                                headcount_entries::Column::HeadcountId,
+                            )
+                            .to(headcounts::Entity, headcounts::Column::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_headcount_entries_resident")
+                            .col(headcount_entries::Column::HeadcountId)
+                            .col(headcount_entries::Column::Doc)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(headcount_entries::Entity).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(headcounts::Entity).to_owned())
+            .await
+    }
+}
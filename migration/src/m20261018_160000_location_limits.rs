@@ -0,0 +1,41 @@
+use entity::locations;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(locations::Entity)
+                    .add_column(ColumnDef::new(locations::Column::Capacity).integer())
+                    .add_column(ColumnDef::new(locations::Column::OpensAt).time())
+                    .add_column(ColumnDef::new(locations::Column::ClosesAt).time())
+                    .add_column(
+                        ColumnDef::new(locations::Column::RejectsOverLimit)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(locations::Entity)
+                    .drop_column(locations::Column::Capacity)
+                    .drop_column(locations::Column::OpensAt)
+                    .drop_column(locations::Column::ClosesAt)
+                    .drop_column(locations::Column::RejectsOverLimit)
+                    .to_owned(),
+            )
+            .await
+    }
+}
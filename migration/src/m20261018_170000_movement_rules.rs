@@ -0,0 +1,153 @@
+use entity::{locations, movement_rules, rejected_scans, resident_restrictions, residents};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(movement_rules::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(movement_rules::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(movement_rules::Column::Location).integer())
+                    .col(
+                        ColumnDef::new(movement_rules::Column::MinLevel)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(movement_rules::Column::StartsAt).time())
+                    .col(ColumnDef::new(movement_rules::Column::EndsAt).time())
+                    .col(
+                        ColumnDef::new(movement_rules::Column::Description)
+                            .string()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_movement_rules_location")
+                            .from(movement_rules::Entity, movement_rules::Column::Location)
+                            .to(locations::Entity, locations::Column::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(resident_restrictions::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(resident_restrictions::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(resident_restrictions::Column::Doc)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(resident_restrictions::Column::Location).integer())
+                    .col(ColumnDef::new(resident_restrictions::Column::SeparateFrom).integer())
+                    .col(
+                        ColumnDef::new(resident_restrictions::Column::Reason)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(resident_restrictions::Column::ExpiresAt).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_resident_restrictions_resident")
+                            .from(
+                                resident_restrictions::Entity,
+                                resident_restrictions::Column::Doc,
+                            )
+                            .to(residents::Entity, residents::Column::Doc)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_resident_restrictions_location")
+                            .from(
+                                resident_restrictions::Entity,
+                                resident_restrictions::Column::Location,
+                            )
+                            .to(locations::Entity, locations::Column::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(rejected_scans::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(rejected_scans::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(rejected_scans::Column::Doc)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(rejected_scans::Column::Location)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(rejected_scans::Column::Ts)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(rejected_scans::Column::DeviceId).integer())
+                    .col(
+                        ColumnDef::new(rejected_scans::Column::Reason)
+                            .string()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_rejected_scans_resident")
+                            .from(rejected_scans::Entity, rejected_scans::Column::Doc)
+                            .to(residents::Entity, residents::Column::Doc)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(rejected_scans::Entity).to_owned())
+            .await?;
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(resident_restrictions::Entity)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(movement_rules::Entity).to_owned())
+            .await
+    }
+}
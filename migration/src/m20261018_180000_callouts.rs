@@ -0,0 +1,86 @@
+use entity::{callouts, locations, residents};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(callouts::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(callouts::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(callouts::Column::Doc).integer().not_null())
+                    .col(
+                        ColumnDef::new(callouts::Column::Location)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(callouts::Column::ArriveFrom)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(callouts::Column::ArriveBy)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(callouts::Column::ReturnBy)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(callouts::Column::Status)
+                            .string()
+                            .not_null()
+                            .default(callouts::PENDING),
+                    )
+                    .col(ColumnDef::new(callouts::Column::ArrivedAt).timestamp())
+                    .col(ColumnDef::new(callouts::Column::ReturnedAt).timestamp())
+                    .col(ColumnDef::new(callouts::Column::Note).string())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_callouts_resident")
+                            .from(callouts::Entity, callouts::Column::Doc)
+                            .to(residents::Entity, residents::Column::Doc)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_callouts_location")
+                            .from(callouts::Entity, callouts::Column::Location)
+                            .to(locations::Entity, locations::Column::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_callouts_doc_status")
+                    .table(callouts::Entity)
+                    .col(callouts::Column::Doc)
+                    .col(callouts::Column::Status)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(callouts::Entity).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,47 @@
+use entity::inventory_event;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // the entity always had this column, inserting an event failed without it
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(inventory_event::Entity)
+                    .add_column_if_not_exists(
+                        ColumnDef::new(inventory_event::Column::PurchaseOrderId).integer(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // a last line of defence, purchases check the locked balance first. Accounts overdrawn before
+        // this would fail it, so it only holds for new writes until they're settled and it's validated
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "ALTER TABLE accounts ADD CONSTRAINT chk_accounts_balance CHECK (balance >= 0) NOT VALID",
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("ALTER TABLE accounts DROP CONSTRAINT chk_accounts_balance")
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(inventory_event::Entity)
+                    .drop_column(inventory_event::Column::PurchaseOrderId)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
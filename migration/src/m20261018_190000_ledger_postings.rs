@@ -0,0 +1,84 @@
+use entity::{accounts, ledger_postings, transactions};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ledger_postings::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ledger_postings::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ledger_postings::Column::TransactionId).integer())
+                    .col(ColumnDef::new(ledger_postings::Column::AccountId).integer())
+                    .col(
+                        ColumnDef::new(ledger_postings::Column::Ledger)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ledger_postings::Column::Amount)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ledger_postings::Column::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_ledger_postings_transaction")
+                            .from(
+                                ledger_postings::Entity,
+                                ledger_postings::Column::TransactionId,
+                            )
+                            .to(transactions::Entity, transactions::Column::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_ledger_postings_account")
+                            .from(ledger_postings::Entity, ledger_postings::Column::AccountId)
+                            .to(accounts::Entity, accounts::Column::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_ledger_postings_account")
+                    .table(ledger_postings::Entity)
+                    .col(ledger_postings::Column::AccountId)
+                    .to_owned(),
+            )
+            .await?;
+        // the postings have to account for the balances accounts already have
+        let db = manager.get_connection();
+        db.execute_unprepared(
+            "INSERT INTO ledger_postings (account_id, ledger, amount)
+             SELECT id, 'resident', balance FROM accounts WHERE balance <> 0
+             UNION ALL
+             SELECT NULL, 'opening', -balance FROM accounts WHERE balance <> 0",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ledger_postings::Entity).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,67 @@
+use entity::{transaction_items, transactions};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(transactions::Entity)
+                    .add_column(ColumnDef::new(transactions::Column::OriginalId).integer())
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_transactions_original")
+                            .from_tbl(transactions::Entity)
+                            .from_col(transactions::Column::OriginalId)
+                            .to_tbl(transactions::Entity)
+                            .to_col(transactions::Column::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(transaction_items::Entity)
+                    .add_column(
+                        ColumnDef::new(transaction_items::Column::Price)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // lines sold before prices were kept are refunded at the price the item has now
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE transaction_items SET price = items.price FROM items WHERE items.id = transaction_items.item_id",
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(transaction_items::Entity)
+                    .drop_column(transaction_items::Column::Price)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(transactions::Entity)
+                    .drop_column(transactions::Column::OriginalId)
+                    .to_owned(),
+            )
+            .await
+    }
+}
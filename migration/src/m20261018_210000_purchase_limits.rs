@@ -0,0 +1,51 @@
+use entity::{items, purchase_limits};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(purchase_limits::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(purchase_limits::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(purchase_limits::Column::Level).integer())
+                    .col(ColumnDef::new(purchase_limits::Column::Doc).integer())
+                    .col(ColumnDef::new(purchase_limits::Column::ItemId).integer())
+                    .col(ColumnDef::new(purchase_limits::Column::WeeklyCap).integer())
+                    .col(ColumnDef::new(purchase_limits::Column::MonthlyCap).integer())
+                    .col(ColumnDef::new(purchase_limits::Column::MaxQuantity).integer())
+                    .col(
+                        ColumnDef::new(purchase_limits::Column::IsRestricted)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_purchase_limits_item")
+                            .from(purchase_limits::Entity, purchase_limits::Column::ItemId)
+                            .to(items::Entity, items::Column::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(purchase_limits::Entity).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,148 @@
+use entity::{inventory_event, items, purchase_order_lines, purchase_orders, users};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(purchase_orders::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(purchase_orders::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(purchase_orders::Column::Vendor)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(purchase_orders::Column::Status)
+                            .string()
+                            .not_null()
+                            .default(purchase_orders::DRAFT),
+                    )
+                    .col(ColumnDef::new(purchase_orders::Column::Note).string())
+                    .col(ColumnDef::new(purchase_orders::Column::CreatedBy).integer())
+                    .col(
+                        ColumnDef::new(purchase_orders::Column::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .col(ColumnDef::new(purchase_orders::Column::OrderedAt).timestamp())
+                    .col(ColumnDef::new(purchase_orders::Column::ReceivedAt).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_purchase_orders_user")
+                            .from(purchase_orders::Entity, purchase_orders::Column::CreatedBy)
+                            .to(users::Entity, users::Column::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(purchase_order_lines::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(purchase_order_lines::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(purchase_order_lines::Column::PurchaseOrderId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(purchase_order_lines::Column::ItemId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(purchase_order_lines::Column::QuantityExpected)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(purchase_order_lines::Column::QuantityReceived)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(purchase_order_lines::Column::UnitCost)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_purchase_order_lines_order")
+                            .from(
+                                purchase_order_lines::Entity,
+                                purchase_order_lines::Column::PurchaseOrderId,
+                            )
+                            .to(purchase_orders::Entity, purchase_orders::Column::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_purchase_order_lines_item")
+                            .from(
+                                purchase_order_lines::Entity,
+                                purchase_order_lines::Column::ItemId,
+                            )
+                            .to(items::Entity, items::Column::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // receipts before this referenced no order, there's nothing for them to point at
+        manager
+            .get_connection()
+            .execute_unprepared("UPDATE inventory_events SET purchase_order_id = NULL")
+            .await?;
+        manager
+            .create_foreign_key(
+                ForeignKey::create()
+                    .name("fk_inventory_events_purchase_order")
+                    .from(
+                        inventory_event::Entity,
+                        inventory_event::Column::PurchaseOrderId,
+                    )
+                    .to(purchase_orders::Entity, purchase_orders::Column::Id)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_foreign_key(
+                ForeignKey::drop()
+                    .name("fk_inventory_events_purchase_order")
+                    .table(inventory_event::Entity)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(purchase_order_lines::Entity).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(purchase_orders::Entity).to_owned())
+            .await
+    }
+}
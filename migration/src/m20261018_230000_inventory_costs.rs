@@ -0,0 +1,114 @@
+use entity::{inventory_event, items, transaction_items};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(items::Entity)
+                    .add_column(
+                        ColumnDef::new(items::Column::UnitCost)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(inventory_event::Entity)
+                    .add_column(
+                        ColumnDef::new(inventory_event::Column::UnitCost)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .add_column(
+                        ColumnDef::new(inventory_event::Column::CreatedAt)
+                            .timestamp()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(transaction_items::Entity)
+                    .add_column(
+                        ColumnDef::new(transaction_items::Column::UnitCost)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        let db = manager.get_connection();
+        // stock already received is costed at what its purchase orders paid on average,
+        // and everything that moved before costs were kept moved at that cost
+        db.execute_unprepared(
+            "UPDATE items SET unit_cost = costs.unit_cost FROM ( \
+                SELECT item_id, ROUND(SUM(quantity_received::numeric * unit_cost) / SUM(quantity_received))::integer AS unit_cost \
+                FROM purchase_order_lines WHERE quantity_received > 0 GROUP BY item_id \
+            ) AS costs WHERE costs.item_id = items.id",
+        )
+        .await?;
+        db.execute_unprepared(
+            "UPDATE inventory_events SET unit_cost = items.unit_cost FROM items WHERE items.id = inventory_events.item_id",
+        )
+        .await?;
+        db.execute_unprepared(
+            "UPDATE transaction_items SET unit_cost = items.unit_cost FROM items WHERE items.id = transaction_items.item_id",
+        )
+        .await?;
+        // stock the events don't account for, so the history adds up to what's on hand
+        db.execute_unprepared(
+            "INSERT INTO inventory_events (item_id, quantity, is_add, unit_cost) \
+            SELECT items.id, ABS(items.quantity - COALESCE(moved.quantity, 0)), items.quantity > COALESCE(moved.quantity, 0), items.unit_cost \
+            FROM items LEFT JOIN ( \
+                SELECT item_id, SUM(CASE WHEN is_add THEN quantity ELSE -quantity END) AS quantity \
+                FROM inventory_events GROUP BY item_id \
+            ) AS moved ON moved.item_id = items.id \
+            WHERE items.quantity <> COALESCE(moved.quantity, 0)",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(transaction_items::Entity)
+                    .drop_column(transaction_items::Column::UnitCost)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(inventory_event::Entity)
+                    .drop_column(inventory_event::Column::UnitCost)
+                    .drop_column(inventory_event::Column::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(items::Entity)
+                    .drop_column(items::Column::UnitCost)
+                    .to_owned(),
+            )
+            .await
+    }
+}
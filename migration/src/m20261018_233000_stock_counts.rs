@@ -0,0 +1,129 @@
+use entity::{inventory_event, items, stock_count_entries, stock_counts, users};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(stock_counts::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(stock_counts::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(stock_counts::Column::Note).string())
+                    .col(ColumnDef::new(stock_counts::Column::StartedBy).integer())
+                    .col(
+                        ColumnDef::new(stock_counts::Column::StartedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(stock_counts::Column::PostedAt).timestamp())
+                    .col(ColumnDef::new(stock_counts::Column::Reason).string())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_stock_counts_user")
+                            .from(stock_counts::Entity, stock_counts::Column::StartedBy)
+                            .to(users::Entity, users::Column::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(stock_count_entries::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(stock_count_entries::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(stock_count_entries::Column::StockCountId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(stock_count_entries::Column::ItemId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(stock_count_entries::Column::Counted)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(stock_count_entries::Column::OnHand).integer())
+                    .col(
+                        ColumnDef::new(stock_count_entries::Column::CountedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_stock_count_entries_count")
+                            .from(
+                                stock_count_entries::Entity,
+                                stock_count_entries::Column::StockCountId,
+                            )
+                            .to(stock_counts::Entity, stock_counts::Column::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_stock_count_entries_item")
+                            .from(
+                                stock_count_entries::Entity,
+                                stock_count_entries::Column::ItemId,
+                            )
+                            .to(items::Entity, items::Column::Id),
+                    )
+                    .index(
+                        Index::create()
+                            .name("idx_stock_count_entries_item")
+                            .col(stock_count_entries::Column::StockCountId)
+                            .col(stock_count_entries::Column::ItemId)
+                            .unique(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(inventory_event::Entity)
+                    .add_column(ColumnDef::new(inventory_event::Column::Reason).string())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(inventory_event::Entity)
+                    .drop_column(inventory_event::Column::Reason)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(stock_count_entries::Entity).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(stock_counts::Entity).to_owned())
+            .await
+    }
+}
@@ -0,0 +1,32 @@
+use entity::items;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(items::Entity)
+                    .add_column(ColumnDef::new(items::Column::ReorderPoint).integer())
+                    .add_column(ColumnDef::new(items::Column::ReorderQuantity).integer())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(items::Entity)
+                    .drop_column(items::Column::ReorderPoint)
+                    .drop_column(items::Column::ReorderQuantity)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,68 @@
+use entity::{auth_sessions, users};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(auth_sessions::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(auth_sessions::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(auth_sessions::Column::UserId)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(auth_sessions::Column::SessionKey)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(auth_sessions::Column::RefreshKey)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(auth_sessions::Column::Ip).string())
+                    .col(
+                        ColumnDef::new(auth_sessions::Column::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(auth_sessions::Column::RefreshedAt).timestamp())
+                    .col(
+                        ColumnDef::new(auth_sessions::Column::ExpiresAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(auth_sessions::Column::RevokedAt).timestamp())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_auth_sessions_user")
+                            .from(auth_sessions::Entity, auth_sessions::Column::UserId)
+                            .to(users::Entity, users::Column::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(auth_sessions::Entity).to_owned())
+            .await
+    }
+}
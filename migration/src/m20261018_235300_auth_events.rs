@@ -0,0 +1,77 @@
+use entity::{auth_events, users};
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(auth_events::Entity)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(auth_events::Column::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(auth_events::Column::UserId).integer())
+                    .col(
+                        ColumnDef::new(auth_events::Column::Email)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(auth_events::Column::Ip).string())
+                    .col(
+                        ColumnDef::new(auth_events::Column::Kind)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(auth_events::Column::CreatedAt)
+                            .timestamp()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_auth_events_user")
+                            .from(auth_events::Entity, auth_events::Column::UserId)
+                            .to(users::Entity, users::Column::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        // lockouts count recent failures by email and by ip
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_auth_events_email")
+                    .table(auth_events::Entity)
+                    .col(auth_events::Column::Email)
+                    .col(auth_events::Column::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_auth_events_ip")
+                    .table(auth_events::Entity)
+                    .col(auth_events::Column::Ip)
+                    .col(auth_events::Column::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(auth_events::Entity).to_owned())
+            .await
+    }
+}
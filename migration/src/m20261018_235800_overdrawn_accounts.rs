@@ -0,0 +1,35 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        // every account overdrawn before purchases were checked gets an `adjustment` transaction bringing
+        // it back to zero against the overdrafts ledger, so what was written off stays in its history
+        db.execute_unprepared(
+            "WITH adjusted AS (
+                 INSERT INTO transactions (doc, account_id, kind, amount, timestamp)
+                 SELECT doc, id, 'adjustment', -balance, LOCALTIMESTAMP FROM accounts WHERE balance < 0
+                 RETURNING id, account_id, amount
+             ), postings AS (
+                 INSERT INTO ledger_postings (transaction_id, account_id, ledger, amount)
+                 SELECT id, account_id, 'resident', amount FROM adjusted
+                 UNION ALL
+                 SELECT id, NULL, 'overdrafts', -amount FROM adjusted
+             )
+             UPDATE accounts SET balance = 0 WHERE balance < 0",
+        )
+        .await?;
+        db.execute_unprepared("ALTER TABLE accounts VALIDATE CONSTRAINT chk_accounts_balance")
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, _manager: &SchemaManager) -> Result<(), DbErr> {
+        // the written off balances are kept, undoing them would overdraw the accounts again
+        Ok(())
+    }
+}
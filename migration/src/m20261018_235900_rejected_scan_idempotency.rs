@@ -0,0 +1,35 @@
+use entity::rejected_scans;
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(rejected_scans::Entity)
+                    .add_column(
+                        ColumnDef::new(rejected_scans::Column::IdempotencyKey)
+                            .string()
+                            .null()
+                            .unique_key(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(rejected_scans::Entity)
+                    .drop_column(rejected_scans::Column::IdempotencyKey)
+                    .to_owned(),
+            )
+            .await
+    }
+}
@@ -0,0 +1,23 @@
+use crate::app_config::DB;
+use std::io::BufRead;
+
+/// `scan_mvcf create-admin <email>` creates the first admin of a fresh install. The password is read from
+/// `ADMIN_PASSWORD`, or a line of stdin so it stays out of the shell history. Refused once any user exists.
+pub async fn create_admin(email: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(email) = email else {
+        return Err("usage: scan_mvcf create-admin <email>".into());
+    };
+    let password = match std::env::var("ADMIN_PASSWORD") {
+        Ok(password) => password,
+        Err(_) => {
+            eprintln!("Password for {email}:");
+            let mut line = String::new();
+            std::io::stdin().lock().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+    let db = DB::get().await?;
+    let user = entity::users::Model::bootstrap_admin(&db.0, &email, &password).await?;
+    println!("Created admin {} (id {})", user.email, user.id);
+    Ok(())
+}
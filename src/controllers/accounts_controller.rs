@@ -2,20 +2,23 @@ use crate::app_config::DB;
 use crate::middleware::auth::Claims;
 use crate::models::response::{FilterOpts, Response};
 use actix_web::http::header::ContentType;
-use actix_web::{get, post, web, HttpResponse, Result};
+use actix_web::{delete, get, post, web, HttpResponse, Result};
+use entity::ledger_postings;
+use entity::levels::Permission;
 use entity::prelude::{Accounts, Transactions};
+use entity::purchase_limits::{self, PostPurchaseLimit};
 
-use entity::transactions::{PostTransaction, TransactionResult};
-use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+use entity::transactions::{PostRefund, PostTransaction, TransactionResult};
+use reqwest::StatusCode;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+};
 
 #[rustfmt::skip]
 #[get("/api/accounts")]
 pub async fn index_accounts(db: web::Data<DB>, auth: Claims, query_params: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    if !auth.is_valid() {
-        let response = Response::<String>::from_error("Unauthorized");
-        return Ok(HttpResponse::Ok()
-            .insert_header(ContentType::json())
-            .json(response));
+    if let Some(response) = auth.require(Permission::Clerk) {
+        return Ok(response);
     }
     let db = &db.0;
     let query_params = query_params.into_inner();
@@ -35,19 +38,16 @@ pub async fn index_accounts(db: web::Data<DB>, auth: Claims, query_params: web::
     } 
         let result = query.all(db).await;
         let response = Response::from_vec(result.unwrap_or_default());
-        return Ok(HttpResponse::Ok()
+        Ok(HttpResponse::Ok()
             .insert_header(ContentType::json())
-            .json(response));
+            .json(response))
 }
 
 #[rustfmt::skip]
 #[get("/api/accounts/{id}")]
 pub async fn show_account(db: web::Data<DB>,id: web::Path<i32>, auth: Claims) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    if !auth.is_valid() {
-        let response = Response::<String>::from_error("Unauthorized");
-        return Ok(HttpResponse::Ok()
-            .insert_header(ContentType::json())
-            .json(response));
+    if let Some(response) = auth.require(Permission::Clerk) {
+        return Ok(response);
     }
     let db = &db.0;
     let id = id.into_inner();
@@ -68,11 +68,8 @@ pub async fn show_account(db: web::Data<DB>,id: web::Path<i32>, auth: Claims) ->
 #[rustfmt::skip]
 #[get("/api/transactions")]
 pub async fn get_all_transactions(db: web::Data<DB>, auth: Claims, query: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    if !auth.is_valid() {
-        let response = Response::<String>::from_error("Unauthorized");
-        return Ok(HttpResponse::Ok()
-            .insert_header(ContentType::json())
-            .json(response));
+    if let Some(response) = auth.require(Permission::Clerk) {
+        return Ok(response);
     }
     let db = &db.0;
     let query_params = query.into_inner();
@@ -90,11 +87,8 @@ pub async fn get_all_transactions(db: web::Data<DB>, auth: Claims, query: web::Q
 #[rustfmt::skip]
 #[get("/api/accounts/{id}/transactions")]
 pub async fn show_account_transactions(db: web::Data<DB>, id: web::Path<i32>, auth: Claims, query: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    if !auth.is_valid() {
-        let response = Response::<String>::from_error("Unauthorized");
-        return Ok(HttpResponse::Ok()
-            .insert_header(ContentType::json())
-            .json(response));
+    if let Some(response) = auth.require(Permission::Clerk) {
+        return Ok(response);
     }
     let query_params = query.into_inner();
     let per_page = query_params.per_page.unwrap_or(10);
@@ -122,26 +116,127 @@ pub async fn show_account_transactions(db: web::Data<DB>, id: web::Path<i32>, au
 #[rustfmt::skip]
 #[post("/api/accounts/{id}/transactions")]
 pub async fn post_transaction(db: web::Data<DB>, id: web::Path<i32>, auth: Claims, transaction: web::Json<PostTransaction>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    if !auth.is_valid() {
-        let response = Response::<String>::from_error("Unauthorized");
-        return Ok(HttpResponse::Ok()
-            .insert_header(ContentType::json())
-            .json(response));
+    if let Some(response) = auth.require(Permission::Clerk) {
+        return Ok(response);
     }
     let db = &db.0;
     let id = id.into_inner();
     match transaction.process_transaction(db, id).await {
         Ok(ref result) => {  
         let response = Response::<TransactionResult>::from_data(result.clone());
-        return Ok(HttpResponse::Ok()
+        Ok(HttpResponse::Ok()
             .insert_header(ContentType::json())
-            .json(response));
+            .json(response))
         } 
         Err(e) => {
             let response = Response::<String>::from_error(e.to_string().as_str());
-            return Ok(HttpResponse::Ok()
+            Ok(HttpResponse::Ok()
             .insert_header(ContentType::json())
-            .json(response));
+            .json(response))
         }
     }
 }
+
+// every account whose stored balance disagrees with its ledger postings, and any unbalanced transaction
+#[rustfmt::skip]
+#[get("/api/accounts/reconciliation")]
+pub async fn reconcile_accounts(db: web::Data<DB>, auth: Claims) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = auth.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let reconciliation = ledger_postings::reconcile(&db.0).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(reconciliation)))
+}
+
+#[rustfmt::skip]
+#[get("/api/accounts/{id}/ledger")]
+pub async fn show_account_ledger(db: web::Data<DB>, id: web::Path<i32>, auth: Claims) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = auth.require(Permission::Clerk) {
+        return Ok(response);
+    }
+    let postings = ledger_postings::Model::for_account(&db.0, id.into_inner()).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_vec(postings)))
+}
+
+// gives back a purchase made today in full
+#[rustfmt::skip]
+#[post("/api/transactions/{id}/void")]
+pub async fn void_transaction(db: web::Data<DB>, id: web::Path<i32>, auth: Claims) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = auth.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    match entity::transactions::Model::void(&db.0, id.into_inner()).await {
+        Ok(result) => Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(result))),
+        Err(e) => Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error(&e.to_string()))),
+    }
+}
+
+// gives back some of the items of a purchase
+#[rustfmt::skip]
+#[post("/api/transactions/{id}/refund")]
+pub async fn refund_transaction(db: web::Data<DB>, id: web::Path<i32>, auth: Claims, refund: web::Json<PostRefund>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = auth.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    match entity::transactions::Model::refund(&db.0, id.into_inner(), &refund).await {
+        Ok(result) => Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(result))),
+        Err(e) => Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error(&e.to_string()))),
+    }
+}
+
+#[rustfmt::skip]
+#[get("/api/purchase_limits")]
+pub async fn index_purchase_limits(db: web::Data<DB>, auth: Claims) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = auth.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let limits = purchase_limits::Entity::find().order_by_asc(purchase_limits::Column::Id).all(&db.0).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_vec(limits)))
+}
+
+// caps spending for a level or resident, or caps or restricts an item for them
+#[rustfmt::skip]
+#[post("/api/purchase_limits")]
+pub async fn store_purchase_limit(db: web::Data<DB>, auth: Claims, limit: web::Json<PostPurchaseLimit>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = auth.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let limit = limit.into_inner();
+    if !limit.is_valid() {
+        let response = Response::<String>::from_error("A limit needs either a level or a doc, and a cap or restriction");
+        return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(response));
+    }
+    let limit = limit.into_active_model().insert(&db.0).await?;
+    Ok(HttpResponse::Ok().status(StatusCode::CREATED).insert_header(ContentType::json()).json(Response::from_data(limit)))
+}
+
+#[rustfmt::skip]
+#[delete("/api/purchase_limits/{id}")]
+pub async fn destroy_purchase_limit(db: web::Data<DB>, auth: Claims, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = auth.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let deleted = purchase_limits::Entity::delete_by_id(id.into_inner()).exec(&db.0).await?;
+    if deleted.rows_affected == 0 {
+        return Ok(HttpResponse::NotFound().insert_header(ContentType::json()).json(Response::<String>::from_error("Limit not found")));
+    }
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::<String>::from_success("Limit deleted")))
+}
+
+// what the resident can still spend and buy this week and month
+#[rustfmt::skip]
+#[get("/api/residents/{doc}/allowance")]
+pub async fn show_allowance(db: web::Data<DB>, auth: Claims, doc: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = auth.require(Permission::Clerk) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let doc = doc.into_inner();
+    let Some(resident) = entity::residents::Entity::find().filter(entity::residents::Column::Doc.eq(doc)).one(db).await? else {
+        return Ok(HttpResponse::NotFound().insert_header(ContentType::json()).json(Response::<String>::from_error("Resident not found")));
+    };
+    let account = Accounts::find().filter(entity::accounts::Column::Doc.eq(doc)).one(db).await?;
+    let now = chrono::Local::now().naive_local();
+    let allowance = purchase_limits::allowance(db, &resident, account.map(|account| account.id), now).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(allowance)))
+}
@@ -1,10 +1,17 @@
 use crate::app_config::DB;
-use crate::middleware::auth::{create_jwt, Claims};
+use crate::middleware::auth::{create_jwt, create_token_pair, validate_jwt, Claims, SESSION_DAYS};
+use crate::middleware::client_ip;
 use crate::models::response::Response;
 use actix_session::Session;
 use actix_web::http::header::ContentType;
-use actix_web::{post, web, HttpRequest, HttpResponse, Result};
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse, Result};
+use entity::auth_events::{self, AuthEventFilter};
+use entity::auth_sessions::{self, RefreshOutcome};
+use entity::levels::Permission;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, IntoActiveModel,
+    PaginatorTrait, QueryFilter, Set,
+};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
@@ -13,45 +20,196 @@ pub struct LoginForm {
     pub password: String,
 }
 
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct PasswordChange {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct RefreshForm {
+    pub refresh_token: String,
+}
+
+async fn permission_level(
+    db: &DatabaseConnection,
+    user: &entity::users::Model,
+) -> Result<i32, DbErr> {
+    Ok(entity::levels::Entity::find_by_id(user.level)
+        .one(db)
+        .await?
+        .map_or(0, |level| level.permission))
+}
+
+fn refused(msg: &str) -> HttpResponse {
+    HttpResponse::Unauthorized()
+        .insert_header(ContentType::json())
+        .json(Response::<String>::from_error(msg))
+}
+
 #[rustfmt::skip]
 #[post("/api/auth/login")]
-pub async fn login(request: HttpRequest, req: Session, claims: Claims, db: web::Data<DB>, form: web::Json<LoginForm>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+pub async fn login(request: HttpRequest, req: Session, claims: Option<Claims>, db: web::Data<DB>, form: web::Json<LoginForm>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
     let db = &db.0;
-    if claims.is_valid() {
+    if claims.is_some() {
             let response = Response::<String>::from_success("Already logged in");
             return Ok(HttpResponse::Ok()
             .insert_header(ContentType::json())
             .json(response));
         }
+    let client_ip = client_ip(&request).unwrap_or_else(|| "<unknown>".to_string());
     let user = entity::users::Entity::find().filter(entity::users::Column::Email.eq(&form.email)).one(db).await?;
-    if let Some(user) = user {
-    let client_info = request.connection_info();
-    let client_ip = String::from(client_info.realip_remote_addr().unwrap_or("<unknown>"));
-           if user.verify_password(&form.password) {
-            let token = create_jwt(&user.email);
+    let user_id = user.as_ref().map(|user| user.id);
+    if auth_events::Model::is_throttled(db, &client_ip).await? || auth_events::Model::is_locked(db, &form.email).await? {
+        auth_events::Model::record(db, auth_events::LOCKED_OUT, &form.email, user_id, Some(client_ip)).await?;
+        let response = Response::<String>::from_error("Too many failed login attempts, try again later");
+        return Ok(HttpResponse::TooManyRequests().insert_header(ContentType::json()).json(response));
+    }
+    // an unknown email still checks a password, so it can't be told apart by the answer or how long it took
+    let verified = match &user {
+        Some(user) => user.verify_password(&form.password),
+        None => entity::users::Model::verify_nobody(&form.password),
+    };
+    match user {
+        Some(user) if verified && !user.is_disabled => {
+            let level = permission_level(db, &user).await?;
+            let session = auth_sessions::Model::start(db, user.id, Some(client_ip.clone()), chrono::Duration::days(SESSION_DAYS as i64)).await?;
+            auth_events::Model::record(db, auth_events::LOGIN, &user.email, Some(user.id), Some(client_ip.clone())).await?;
+            let token = create_jwt(&user, level, &session);
               req.insert("token", token.clone())?;
               req.insert("user_id", user.id)?;
               req.insert("ip", client_ip)?;
-              req.insert("exp",chrono::Local::now() + chrono::Duration::days(7))?;
-            let response = Response::<String>::from_success("successfully logged in");
+              req.insert("exp", session.expires_at)?;
+            // the same login as the cookie, for clients sending an Authorization header instead
+            let mut response = Response::from_data(create_token_pair(&user, level, &session));
+            response.message = "successfully logged in".to_string();
              Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(response))
-        } else {
-            let response = Response::<String>::from_error("Invalid password");
-            return Ok(HttpResponse::Ok()
-            .insert_header(ContentType::json())
-            .json(response));
         }
-    } else {
-        let response = Response::<String>::from_error("User not found");
-        return Ok(HttpResponse::Ok()
-            .insert_header(ContentType::json())
-            .json(response));
+        _ => {
+            auth_events::Model::record(db, auth_events::FAILED_LOGIN, &form.email, user_id, Some(client_ip)).await?;
+            Ok(refused("Invalid email or password"))
+        }
     }
 }
 
 #[rustfmt::skip]
 #[post("/api/auth/logout")]
-pub async fn logout(session: Session) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+pub async fn logout(session: Session, claims: Option<Claims>, db: web::Data<DB>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(claims) = claims {
+        let db = &db.0;
+        let current = auth_sessions::Model::find_active(db, claims.session_key()).await?;
+        auth_sessions::Model::revoke(db, claims.session_key()).await?;
+        let ip = current.as_ref().and_then(|session| session.ip.clone());
+        auth_events::Model::record(db, auth_events::LOGOUT, claims.subject(), current.map(|session| session.user_id), ip).await?;
+    }
     session.purge();
     Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::<String>::from_success("successfully logged out")))
 }
+
+// the only thing a user with a reset password can do, every other login of theirs is logged out
+#[rustfmt::skip]
+#[post("/api/auth/password")]
+pub async fn change_password(req: Session, claims: Claims, db: web::Data<DB>, form: web::Json<PasswordChange>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    let db = &db.0;
+    let Some(current) = auth_sessions::Model::find_active(db, claims.session_key()).await? else {
+        return Ok(refused("Session expired, please log in again"));
+    };
+    let Some(user) = entity::users::Entity::find_by_id(current.user_id).one(db).await? else {
+        return Ok(refused("User not found"));
+    };
+    if !user.verify_password(&form.current_password) {
+        // counts towards the lockout like any other wrong password
+        auth_events::Model::record(db, auth_events::FAILED_LOGIN, &user.email, Some(user.id), current.ip.clone()).await?;
+        return Ok(refused("Current password is incorrect"));
+    }
+    if form.new_password == form.current_password {
+        return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error("The new password must be different")));
+    }
+    let password = match entity::users::Model::hash_password(&form.new_password) {
+        Ok(password) => password,
+        Err(e) => return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error(&e))),
+    };
+    let mut active = user.into_active_model();
+    active.password = Set(password);
+    active.must_change_password = Set(false);
+    let user = active.update(db).await?;
+    auth_sessions::Model::revoke_user(db, user.id, Some(current.id)).await?;
+    auth_events::Model::record(db, auth_events::PASSWORD_CHANGED, &user.email, Some(user.id), current.ip.clone()).await?;
+    let level = permission_level(db, &user).await?;
+    if req.get::<String>("token")?.is_some() {
+        req.insert("token", create_jwt(&user, level, &current))?;
+    }
+    let mut response = Response::from_data(create_token_pair(&user, level, &current));
+    response.message = "Password changed".to_string();
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(response))
+}
+
+// trades a refresh token for a new access and refresh token, the old refresh token stops working
+#[rustfmt::skip]
+#[post("/api/auth/refresh")]
+pub async fn refresh(request: HttpRequest, db: web::Data<DB>, form: web::Json<RefreshForm>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    let db = &db.0;
+    let Some(claims) = validate_jwt(&form.refresh_token).filter(|claims| claims.is_valid()) else {
+        return Ok(refused("Invalid refresh token"));
+    };
+    let Some(refresh_key) = claims.refresh_key() else {
+        return Ok(refused("Invalid refresh token"));
+    };
+    match auth_sessions::Model::refresh(db, claims.session_key(), refresh_key).await? {
+        RefreshOutcome::Refreshed(session) => {
+            let Some(user) = entity::users::Entity::find_by_id(session.user_id).one(db).await?.filter(|user| !user.is_disabled) else {
+                return Ok(refused("User not found"));
+            };
+            // picks up a level changed since the login
+            let level = permission_level(db, &user).await?;
+            Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(create_token_pair(&user, level, &session))))
+        }
+        RefreshOutcome::Reused => {
+            auth_events::Model::record(db, auth_events::REFRESH_REUSED, claims.subject(), None, client_ip(&request)).await?;
+            Ok(refused("Refresh token was already used, the session has been revoked"))
+        }
+        RefreshOutcome::Inactive => Ok(refused("Session expired, please log in again")),
+    }
+}
+
+// the logged in user's sessions that haven't been revoked or expired
+#[rustfmt::skip]
+#[get("/api/auth/sessions")]
+pub async fn index_sessions(db: web::Data<DB>, claims: Claims) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    let db = &db.0;
+    let Some(current) = auth_sessions::Model::find_active(db, claims.session_key()).await? else {
+        return Ok(refused("Session expired, please log in again"));
+    };
+    let sessions = auth_sessions::Model::active_for_user(db, current.user_id).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_vec(sessions)))
+}
+
+// revokes one of the user's own sessions, or anyone's for an admin
+#[rustfmt::skip]
+#[delete("/api/auth/sessions/{id}")]
+pub async fn revoke_session(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    let db = &db.0;
+    let Some(current) = auth_sessions::Model::find_active(db, claims.session_key()).await? else {
+        return Ok(refused("Session expired, please log in again"));
+    };
+    let session = auth_sessions::Entity::find_by_id(id.into_inner()).one(db).await?;
+    let Some(session) = session.filter(|session| session.user_id == current.user_id || claims.has_permission(Permission::Admin)) else {
+        return Ok(HttpResponse::NotFound().insert_header(ContentType::json()).json(Response::<String>::from_error("Session not found")));
+    };
+    auth_sessions::Model::revoke(db, &session.session_key).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::<String>::from_success("Session revoked")))
+}
+
+// login history, filtered by `user_id`, `email`, `ip`, `kind` and a `start`/`end` date
+#[rustfmt::skip]
+#[get("/api/auth/events")]
+pub async fn index_auth_events(db: web::Data<DB>, claims: Claims, query: web::Query<AuthEventFilter>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Admin) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let paginator = query.query().paginate(db, query.per_page.unwrap_or(50));
+    let page = paginator.fetch_page(query.page.unwrap_or(1).saturating_sub(1)).await?;
+    let total = paginator.num_items_and_pages().await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_paginator(&total, page)))
+}
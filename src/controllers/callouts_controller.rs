@@ -0,0 +1,75 @@
+use crate::app_config::DB;
+use crate::middleware::auth::Claims;
+use crate::models::response::{FilterOpts, Response};
+use actix_web::http::header::ContentType;
+use actix_web::{delete, get, post, web, HttpResponse};
+use entity::callouts::{self, PostCallout};
+use entity::levels::Permission;
+use reqwest::StatusCode;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+};
+
+#[rustfmt::skip]
+#[get("/api/callouts")]
+pub async fn index_callouts(db: web::Data<DB>, claims: Claims, query: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let params = query.into_inner();
+    let mut query = callouts::Entity::find().order_by_asc(callouts::Column::ArriveBy);
+    if let Some(location) = params.location {
+        query = query.filter(callouts::Column::Location.eq(location));
+    }
+    if let Some(doc) = params.doc {
+        query = query.filter(callouts::Column::Doc.eq(doc));
+    }
+    if let Some(range) = params.get_range() {
+        query = query.filter(callouts::Column::ArriveBy.between(range.0.naive_utc(), range.1.naive_utc()));
+    }
+    let paginator = query.paginate(db, params.per_page.unwrap_or(10));
+    let now = chrono::Local::now().naive_local();
+    let page = paginator.fetch_page(params.page.unwrap_or(1).saturating_sub(1)).await?.into_iter().map(|callout| callout.with_status_at(now)).collect();
+    let total = paginator.num_items_and_pages().await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_paginator(&total, page)))
+}
+
+// residents who haven't shown up at the location yet, or haven't left it by their return time
+#[rustfmt::skip]
+#[get("/api/locations/{id}/overdue")]
+pub async fn overdue_callouts(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
+    let overdue = callouts::Model::overdue_at(&db.0, id.into_inner()).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_vec(overdue)))
+}
+
+#[rustfmt::skip]
+#[post("/api/callouts")]
+pub async fn store_callout(db: web::Data<DB>, claims: Claims, callout: web::Json<PostCallout>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let callout = callout.into_inner();
+    if !callout.is_valid() {
+        let response = Response::<String>::from_error("A callout needs arrive_from <= arrive_by <= return_by");
+        return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(response));
+    }
+    let callout = callout.into_active_model().insert(&db.0).await?;
+    Ok(HttpResponse::Ok().status(StatusCode::CREATED).insert_header(ContentType::json()).json(Response::from_data(callout)))
+}
+
+#[rustfmt::skip]
+#[delete("/api/callouts/{id}")]
+pub async fn destroy_callout(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let deleted = callouts::Entity::delete_by_id(id.into_inner()).exec(&db.0).await?;
+    if deleted.rows_affected == 0 {
+        return Ok(HttpResponse::NotFound().insert_header(ContentType::json()).json(Response::<String>::from_error("Callout not found")));
+    }
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::<String>::from_success("Callout deleted")))
+}
@@ -0,0 +1,62 @@
+use crate::app_config::DB;
+use crate::middleware::auth::Claims;
+use crate::models::response::Response;
+use actix_web::http::header::ContentType;
+use actix_web::{delete, get, post, web, HttpResponse, Result};
+use entity::devices::{self, PostDevice, RegisteredDevice};
+use entity::levels::Permission;
+use reqwest::StatusCode;
+use sea_orm::{ActiveModelTrait, EntityTrait, IntoActiveModel, Set};
+
+#[rustfmt::skip]
+#[get("/api/devices")]
+pub async fn index_devices(db: web::Data<DB>, claims: Claims) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Admin) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let devices = devices::Entity::find().all(db).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_vec(devices)))
+}
+
+// register a scanner for a location, the api key is only ever shown in this response
+#[rustfmt::skip]
+#[post("/api/devices")]
+pub async fn register_device(db: web::Data<DB>, claims: Claims, device: web::Json<PostDevice>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Admin) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let device = device.into_inner();
+    if entity::locations::Entity::find_by_id(device.location).one(db).await?.is_none() {
+        let response = Response::<String>::from_error("Error registering device, location not found");
+        return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(response));
+    }
+    let (device, api_key) = device.into_active_model();
+    let device = device.insert(db).await?;
+    let response = Response::from_data(RegisteredDevice {
+        id: device.id,
+        name: device.name,
+        location: device.location,
+        api_key,
+    });
+    Ok(HttpResponse::Ok().status(StatusCode::CREATED).insert_header(ContentType::json()).json(response))
+}
+
+// revoked devices are kept so the timestamps they recorded still point to them
+#[rustfmt::skip]
+#[delete("/api/devices/{id}")]
+pub async fn revoke_device(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Admin) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    if let Some(device) = devices::Entity::find_by_id(id.into_inner()).one(db).await? {
+        let mut device = device.into_active_model();
+        device.is_revoked = Set(true);
+        device.update(db).await?;
+        Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::<String>::from_success("Device revoked")))
+    } else {
+        Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::<String>::from_error("Error revoking device, device not found")))
+    }
+}
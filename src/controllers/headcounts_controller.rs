@@ -0,0 +1,109 @@
+use crate::app_config::DB;
+use crate::middleware::auth::Claims;
+use crate::models::response::{FilterOpts, Response};
+use actix_web::http::header::ContentType;
+use actix_web::{get, post, web, HttpResponse};
+use entity::headcounts::{self, CountScan, CountScanOutcome, PostHeadcount};
+use entity::levels::Permission;
+use entity::users;
+use reqwest::StatusCode;
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder};
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound()
+        .insert_header(ContentType::json())
+        .json(Response::<String>::from_error("Headcount not found"))
+}
+
+// past counts, newest first
+#[rustfmt::skip]
+#[get("/api/headcounts")]
+pub async fn index_headcounts(db: web::Data<DB>, claims: Claims, query: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let params = query.into_inner();
+    let mut query = headcounts::Entity::find().order_by_desc(headcounts::Column::StartedAt);
+    if let Some(location) = params.location {
+        query = query.filter(headcounts::Column::Location.eq(location));
+    }
+    let paginator = query.paginate(db, params.per_page.unwrap_or(10));
+    let page = paginator.fetch_page(params.page.unwrap_or(1).saturating_sub(1)).await?;
+    let total = paginator.num_items_and_pages().await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_paginator(&total, page)))
+}
+
+// snapshot who should be at the location (or anywhere in the facility) and open the count
+#[rustfmt::skip]
+#[post("/api/headcounts")]
+pub async fn start_headcount(db: web::Data<DB>, claims: Claims, headcount: web::Json<PostHeadcount>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let headcount = headcount.into_inner();
+    if let Some(location) = headcount.location {
+        if entity::locations::Entity::find_by_id(location).one(db).await?.is_none() {
+            return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error("Location not found")));
+        }
+    }
+    let Some(user) = users::Model::find_by_email(db, claims.subject()).await? else {
+        return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error("User not found")));
+    };
+    let report = headcount.start(db, user.id).await?.report(db).await?;
+    Ok(HttpResponse::Ok().status(StatusCode::CREATED).insert_header(ContentType::json()).json(Response::from_data(report)))
+}
+
+// the count with everyone still missing, found unexpectedly or signed out
+#[rustfmt::skip]
+#[get("/api/headcounts/{id}")]
+pub async fn show_headcount(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let Some(headcount) = headcounts::Entity::find_by_id(id.into_inner()).one(db).await? else {
+        return Ok(not_found());
+    };
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(headcount.report(db).await?)))
+}
+
+#[rustfmt::skip]
+#[post("/api/headcounts/{id}/scan")]
+pub async fn scan_headcount(db: web::Data<DB>, claims: Claims, id: web::Path<i32>, scan: web::Json<CountScan>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let Some(headcount) = headcounts::Entity::find_by_id(id.into_inner()).one(db).await? else {
+        return Ok(not_found());
+    };
+    match headcount.confirm(db, &scan.rfid).await? {
+        CountScanOutcome::Confirmed(entry) => Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(entry))),
+        CountScanOutcome::AlreadyConfirmed(entry) => {
+            let mut response = Response::from_data(entry);
+            response.message = "Resident already counted".to_string();
+            Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(response))
+        }
+        CountScanOutcome::Closed => Ok(HttpResponse::Conflict().insert_header(ContentType::json()).json(Response::<String>::from_error("Headcount is closed"))),
+        CountScanOutcome::NotFound => Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error("Resident not found"))),
+    }
+}
+
+#[rustfmt::skip]
+#[post("/api/headcounts/{id}/close")]
+pub async fn close_headcount(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let Some(headcount) = headcounts::Entity::find_by_id(id.into_inner()).one(db).await? else {
+        return Ok(not_found());
+    };
+    if !headcount.is_open() {
+        return Ok(HttpResponse::Conflict().insert_header(ContentType::json()).json(Response::<String>::from_error("Headcount is already closed")));
+    }
+    let report = headcount.close(db).await?.report(db).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(report)))
+}
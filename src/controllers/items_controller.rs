@@ -5,18 +5,25 @@ use crate::{
 };
 use actix_web::{get, http::header::ContentType, patch, post, web, HttpResponse};
 use entity::items::{CreateItem, Entity as Item};
+use entity::levels::Permission;
+use entity::purchase_orders::PostReorder;
+use entity::users;
 use reqwest::StatusCode;
 use sea_orm::{EntityTrait, PaginatorTrait, TryIntoModel};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct LowStockOpts {
+    /// days of sales to average, 30 by default
+    pub days: Option<i64>,
+}
 
 #[rustfmt::skip]
 #[get("/api/items")]
 pub async fn index_items(db: web::Data<DB>, auth: Claims, query: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    if !auth.is_valid() {
-        let response = Response::<String>::from_error("Unauthorized");
-        return Ok(HttpResponse::Unauthorized()
-            .insert_header(ContentType::json())
-            .json(response));
-    };
+    if let Some(response) = auth.require(Permission::Clerk) {
+        return Ok(response);
+    }
     let db = &db.0;
     let query = query.into_inner();
     let per_page = query.per_page.unwrap_or(10);
@@ -30,17 +37,47 @@ pub async fn index_items(db: web::Data<DB>, auth: Claims, query: web::Query<Filt
         .json(response))
 }
 
+// items at or below their reorder point, with how long their stock lasts at the rate they sell
+#[rustfmt::skip]
+#[get("/api/items/low_stock")]
+pub async fn low_stock_items(db: web::Data<DB>, auth: Claims, query: web::Query<LowStockOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = auth.require(Permission::Clerk) {
+        return Ok(response);
+    }
+    let days = query.days.unwrap_or(30);
+    if days < 1 {
+        return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error("Sales have to be averaged over at least a day")));
+    }
+    let items = entity::items::low_stock(&db.0, days).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_vec(items)))
+}
+
+// drafts a purchase order for the suggested quantity of every low item
+#[rustfmt::skip]
+#[post("/api/items/low_stock/reorder")]
+pub async fn reorder_low_stock(db: web::Data<DB>, auth: Claims, reorder: web::Json<PostReorder>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = auth.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let user = users::Model::find_by_email(db, auth.subject()).await?;
+    match reorder.into_inner().create(db, user.map(|user| user.id)).await {
+        Ok(detail) => Ok(HttpResponse::Ok().status(StatusCode::CREATED).insert_header(ContentType::json()).json(Response::from_data(detail))),
+        Err(e) => Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error(&e.to_string()))),
+    }
+}
+
 #[rustfmt::skip]
 #[patch("/api/items/{id}")]
 pub async fn update_item(db: web::Data<DB>, auth: Claims, id: web::Path<i32>, item: web::Json<entity::items::PatchItem>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    if !auth.is_valid() {
-        let response = Response::<String>::from_error("Unauthorized");
-        return Ok(HttpResponse::Unauthorized()
-            .insert_header(ContentType::json())
-            .json(response));
+    if let Some(response) = auth.require(Permission::Supervisor) {
+        return Ok(response);
     }
     let db = &db.0;
     let id = id.into_inner();
+    if !item.is_valid() {
+        return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error("Unknown adjustment reason, or an invalid reorder point or quantity")));
+    }
     let result = item.update_item(id, db).await?;
     let item = result.try_into_model()?;
     let response = Response::<entity::items::Model>::from_data(item);
@@ -52,14 +89,11 @@ pub async fn update_item(db: web::Data<DB>, auth: Claims, id: web::Path<i32>, it
 #[rustfmt::skip]
 #[post("/api/items")]
 pub async fn create_item(db: web::Data<DB>, auth: Claims, item: web::Json<CreateItem>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    if !auth.is_valid() {
-        let response = Response::<String>::from_error("Unauthorized");
-        return Ok(HttpResponse::Unauthorized()
-            .insert_header(ContentType::json())
-            .json(response));
+    if let Some(response) = auth.require(Permission::Supervisor) {
+        return Ok(response);
     }
     let db = &db.0;
-    let _ = Item::insert(item.into_inner().into_active_model()).exec(db).await?;
+    item.into_inner().create(db).await?;
     let response = Response::<String>::from_success("Item created successfully");
     Ok(HttpResponse::Ok()
         .status(StatusCode::CREATED)
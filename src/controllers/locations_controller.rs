@@ -1,16 +1,24 @@
 use crate::app_config::DB;
+use crate::events::ScanHub;
+use crate::middleware::auth::Claims;
 use crate::models::response::{FilterOpts, Response};
-use actix_web::http::header::ContentType;
+use actix_web::http::header::{CacheControl, CacheDirective, ContentType};
 use actix_web::{delete, get, patch, post, web, HttpResponse, Responder, ResponseError};
+use entity::levels::Permission;
 use entity::residents;
 use entity::{
-    locations::{self, Entity as Locations},
+    locations::{self, Entity as Locations, PatchLocation},
     residents::Entity as Residents,
 };
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, PaginatorTrait, QueryFilter, Set,
 };
 use serde::Deserialize;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+// how often an idle event stream sends a comment so proxies and clients keep it open
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
 
 #[derive(Debug, Deserialize)]
 pub struct LocationsError(pub String);
@@ -25,7 +33,10 @@ impl std::fmt::Display for LocationsError {
 // index all locations
 #[rustfmt::skip]
 #[get("/api/locations")]
-pub async fn index(db: web::Data<DB>, query: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+pub async fn index(db: web::Data<DB>, claims: Claims, query: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
     let db = &db.0;
     let query_params = query.into_inner();
     if let Some(true) = query_params.all {
@@ -44,13 +55,21 @@ pub async fn index(db: web::Data<DB>, query: web::Query<FilterOpts>) -> Result<H
 // add a new location
 #[rustfmt::skip]
 #[post("/api/locations")]
-pub async fn store(db: web::Data<DB>, loc: web::Json<locations::Model>) -> impl Responder {
+pub async fn store(db: web::Data<DB>, claims: Claims, loc: web::Json<locations::Model>) -> impl Responder {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return response;
+    }
     let db = &db.0;
     log::info!("POST: locations controller");
     let loc = loc.into_inner();
     let location = locations::ActiveModel {
         id: Set(loc.id),
         name: Set(loc.name),
+        is_work: Set(loc.is_work),
+        capacity: Set(loc.capacity),
+        opens_at: Set(loc.opens_at),
+        closes_at: Set(loc.closes_at),
+        rejects_over_limit: Set(loc.rejects_over_limit),
         ..Default::default()
     };
     if Locations::insert(location).exec(db).await.is_ok() {
@@ -63,13 +82,15 @@ pub async fn store(db: web::Data<DB>, loc: web::Json<locations::Model>) -> impl
 
 #[rustfmt::skip]
 #[patch("/api/locations/{location_id}")]
-pub async fn update(db: web::Data<DB>, id: web::Path<i32>, loc: web::Json<locations::Model>) -> impl Responder {
+pub async fn update(db: web::Data<DB>, claims: Claims, id: web::Path<i32>, loc: web::Json<PatchLocation>) -> impl Responder {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return response;
+    }
     let db = &db.0;
     let id = id.into_inner();
     if let Some(location) = Locations::find_by_id(id).one(db).await.unwrap_or(None) {
         let mut active = location.into_active_model();
-        let loc = loc.into_inner();
-        active.name = Set(loc.name);
+        loc.into_inner().apply(&mut active);
         if active.save(db).await.is_ok() {
             let resp: Response<String> = Response::from_success("Location successfully updated");
             HttpResponse::Ok().insert_header(ContentType::json()).json(resp)
@@ -80,9 +101,23 @@ pub async fn update(db: web::Data<DB>, id: web::Path<i32>, loc: web::Json<locati
         HttpResponse::Ok().insert_header(ContentType::json()).json(Response::<String>::from_error("Error updating location, location not found"))
     }
 }
+// residents at every location against its capacity and hours
+#[rustfmt::skip]
+#[get("/api/locations/occupancy")]
+pub async fn occupancy(db: web::Data<DB>, claims: Claims) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
+    let occupancy = locations::Model::occupancy(&db.0).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_vec(occupancy)))
+}
+
 // Get location name from ID
 #[get("/api/locations/{location_id}")]
-pub async fn show(db: web::Data<DB>, id: web::Path<i32>) -> impl Responder {
+pub async fn show(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> impl Responder {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return response;
+    }
     let db = &db.0;
     let id = id.into_inner();
     log::info!("GET: Locations Show: {}", id);
@@ -102,7 +137,10 @@ pub async fn show(db: web::Data<DB>, id: web::Path<i32>) -> impl Responder {
 // show all residents for a given location
 #[rustfmt::skip]
 #[get("/api/locations/{location_id}/residents")]
-pub async fn show_location_residents(db: web::Data<DB>, id: web::Path<i32>, curr: web::Query<FilterOpts>) -> impl Responder  {
+pub async fn show_location_residents(db: web::Data<DB>, claims: Claims, id: web::Path<i32>, curr: web::Query<FilterOpts>) -> impl Responder  {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return response;
+    }
     let db = &db.0;
     let id = id.into_inner();
     let curr = curr.into_inner();
@@ -139,9 +177,41 @@ pub async fn show_location_residents(db: web::Data<DB>, id: web::Path<i32>, curr
     }
 }
 
+// stream every scan into or out of a location as server-sent events, so Monitors don't need to poll
+#[rustfmt::skip]
+#[get("/api/locations/{location_id}/events")]
+pub async fn location_events(hub: web::Data<ScanHub>, claims: Claims, id: web::Path<i32>) -> impl Responder {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return response;
+    }
+    let id = id.into_inner();
+    let stream = futures::stream::unfold(hub.subscribe(), move |mut receiver| async move {
+        loop {
+            let message = match tokio::time::timeout(KEEP_ALIVE, receiver.recv()).await {
+                Ok(Ok(event)) if event.concerns(id) => {
+                    format!("event: scan\ndata: {}\n\n", serde_json::to_string(&event).unwrap_or_default())
+                }
+                Ok(Ok(_)) => continue,
+                // the client fell behind and missed events, it has to re-fetch the residents list
+                Ok(Err(RecvError::Lagged(_))) => String::from("event: resync\ndata: {}\n\n"),
+                Ok(Err(RecvError::Closed)) => return None,
+                Err(_) => String::from(": keep-alive\n\n"),
+            };
+            return Some((Ok::<_, actix_web::Error>(web::Bytes::from(message)), receiver));
+        }
+    });
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .streaming(stream)
+}
+
 #[rustfmt::skip]
 #[delete("/api/locations/{location_id}")]
-pub async fn destroy(db: web::Data<DB>, id: web::Path<i32>) -> impl Responder {
+pub async fn destroy(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> impl Responder {
+    if let Some(response) = claims.require(Permission::Admin) {
+        return response;
+    }
     let db = &db.0;
     let id = id.into_inner();
     if Locations::find_by_id(id).one(db).await.unwrap_or(None).is_some() {
@@ -13,3 +13,17 @@ pub mod user_controller;
 pub mod order_controller;
 
 pub mod items_controller;
+
+pub mod devices_controller;
+
+pub mod reports_controller;
+
+pub mod headcounts_controller;
+
+pub mod movement_controller;
+
+pub mod callouts_controller;
+
+pub mod purchase_orders_controller;
+
+pub mod stock_counts_controller;
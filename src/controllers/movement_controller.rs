@@ -0,0 +1,111 @@
+use crate::app_config::DB;
+use crate::middleware::auth::Claims;
+use crate::models::response::{FilterOpts, Response};
+use actix_web::http::header::ContentType;
+use actix_web::{delete, get, post, web, HttpResponse};
+use entity::levels::Permission;
+use entity::movement_rules::{self, PostMovementRule};
+use entity::rejected_scans;
+use entity::resident_restrictions::{self, PostRestriction};
+use reqwest::StatusCode;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+};
+
+#[rustfmt::skip]
+#[get("/api/movement_rules")]
+pub async fn index_movement_rules(db: web::Data<DB>, claims: Claims) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let rules = movement_rules::Entity::find().order_by_asc(movement_rules::Column::Id).all(&db.0).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_vec(rules)))
+}
+
+#[rustfmt::skip]
+#[post("/api/movement_rules")]
+pub async fn store_movement_rule(db: web::Data<DB>, claims: Claims, rule: web::Json<PostMovementRule>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let rule = rule.into_inner().into_active_model().insert(&db.0).await?;
+    Ok(HttpResponse::Ok().status(StatusCode::CREATED).insert_header(ContentType::json()).json(Response::from_data(rule)))
+}
+
+#[rustfmt::skip]
+#[delete("/api/movement_rules/{id}")]
+pub async fn destroy_movement_rule(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let deleted = movement_rules::Entity::delete_by_id(id.into_inner()).exec(&db.0).await?;
+    if deleted.rows_affected == 0 {
+        return Ok(HttpResponse::NotFound().insert_header(ContentType::json()).json(Response::<String>::from_error("Rule not found")));
+    }
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::<String>::from_success("Rule deleted")))
+}
+
+#[rustfmt::skip]
+#[get("/api/residents/{doc}/restrictions")]
+pub async fn index_restrictions(db: web::Data<DB>, claims: Claims, doc: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let now = chrono::Local::now().naive_local();
+    let restrictions = resident_restrictions::Model::active_for(&db.0, doc.into_inner(), now).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_vec(restrictions)))
+}
+
+// keep a resident out of a location, or apart from another resident
+#[rustfmt::skip]
+#[post("/api/residents/{doc}/restrictions")]
+pub async fn store_restriction(db: web::Data<DB>, claims: Claims, doc: web::Path<i32>, restriction: web::Json<PostRestriction>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let restriction = restriction.into_inner();
+    if !restriction.is_valid() {
+        let response = Response::<String>::from_error("A restriction needs a reason and either a location or a resident to keep separate from");
+        return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(response));
+    }
+    let restriction = restriction.into_active_model(doc.into_inner()).insert(&db.0).await?;
+    Ok(HttpResponse::Ok().status(StatusCode::CREATED).insert_header(ContentType::json()).json(Response::from_data(restriction)))
+}
+
+#[rustfmt::skip]
+#[delete("/api/restrictions/{id}")]
+pub async fn destroy_restriction(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let deleted = resident_restrictions::Entity::delete_by_id(id.into_inner()).exec(&db.0).await?;
+    if deleted.rows_affected == 0 {
+        return Ok(HttpResponse::NotFound().insert_header(ContentType::json()).json(Response::<String>::from_error("Restriction not found")));
+    }
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::<String>::from_success("Restriction deleted")))
+}
+
+// denied scans for staff to follow up on, newest first
+#[rustfmt::skip]
+#[get("/api/rejected_scans")]
+pub async fn index_rejected_scans(db: web::Data<DB>, claims: Claims, query: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let params = query.into_inner();
+    let mut query = rejected_scans::Entity::find().order_by_desc(rejected_scans::Column::Ts);
+    if let Some(location) = params.location {
+        query = query.filter(rejected_scans::Column::Location.eq(location));
+    }
+    if let Some(doc) = params.doc {
+        query = query.filter(rejected_scans::Column::Doc.eq(doc));
+    }
+    if let Some(range) = params.get_range() {
+        query = query.filter(rejected_scans::Column::Ts.between(range.0.naive_utc(), range.1.naive_utc()));
+    }
+    let paginator = query.paginate(db, params.per_page.unwrap_or(10));
+    let page = paginator.fetch_page(params.page.unwrap_or(1).saturating_sub(1)).await?;
+    let total = paginator.num_items_and_pages().await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_paginator(&total, page)))
+}
@@ -9,7 +9,7 @@ use actix_web::{
     web::{Data, Query},
     HttpResponse,
 };
-use entity::{prelude::OrmSerializable, transaction_items::Entity as Orders};
+use entity::{levels::Permission, prelude::OrmSerializable, transaction_items::Entity as Orders};
 use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
 use serde::{Deserialize, Serialize};
 use std::ops::Sub;
@@ -55,9 +55,8 @@ impl ReturnOrder {
 #[rustfmt::skip]
 #[get("/api/orders")]
 pub async fn get_orders(auth: Claims, db: Data<DB>, params: Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    if !auth.is_valid() {
-       let response = Response::<String>::from_error("Unauthorized");
-       return Ok(HttpResponse::Ok().json(response));
+    if let Some(response) = auth.require(Permission::Clerk) {
+        return Ok(response);
     }
     let db = &db.0;
     let query_params = params.into_inner();
@@ -87,7 +86,7 @@ pub async fn get_orders(auth: Claims, db: Data<DB>, params: Query<FilterOpts>) -
         let num = items.num_items_and_pages().await?;
         let items = items.fetch_page(page.saturating_sub(1)).await?;
         let response = Response::from_paginator(&num, items);
-        return Ok(HttpResponse::Ok()
+        Ok(HttpResponse::Ok()
             .insert_header(ContentType::json())
-            .json(response));
+            .json(response))
 }
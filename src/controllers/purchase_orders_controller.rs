@@ -0,0 +1,106 @@
+use crate::app_config::DB;
+use crate::middleware::auth::Claims;
+use crate::models::response::{FilterOpts, Response};
+use actix_web::http::header::ContentType;
+use actix_web::{get, post, web, HttpResponse};
+use entity::levels::Permission;
+use entity::purchase_orders::{self, PostPurchaseOrder, PostReceipt, PurchaseOrderDetail};
+use entity::users;
+use reqwest::StatusCode;
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder};
+
+fn bad_request(msg: &str) -> HttpResponse {
+    HttpResponse::BadRequest()
+        .insert_header(ContentType::json())
+        .json(Response::<String>::from_error(msg))
+}
+
+fn detail_response(
+    result: Result<PurchaseOrderDetail, Box<dyn std::error::Error>>,
+) -> HttpResponse {
+    match result {
+        Ok(detail) => HttpResponse::Ok()
+            .insert_header(ContentType::json())
+            .json(Response::from_data(detail)),
+        Err(e) => bad_request(&e.to_string()),
+    }
+}
+
+#[rustfmt::skip]
+#[get("/api/purchase_orders")]
+pub async fn index_purchase_orders(db: web::Data<DB>, claims: Claims, query: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Clerk) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let params = query.into_inner();
+    let mut query = purchase_orders::Entity::find().order_by_desc(purchase_orders::Column::CreatedAt);
+    if let Some(range) = params.get_range() {
+        query = query.filter(purchase_orders::Column::CreatedAt.between(range.0.naive_utc(), range.1.naive_utc()));
+    }
+    let paginator = query.paginate(db, params.per_page.unwrap_or(10));
+    let page = paginator.fetch_page(params.page.unwrap_or(1).saturating_sub(1)).await?;
+    let total = paginator.num_items_and_pages().await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_paginator(&total, page)))
+}
+
+#[rustfmt::skip]
+#[post("/api/purchase_orders")]
+pub async fn store_purchase_order(db: web::Data<DB>, claims: Claims, order: web::Json<PostPurchaseOrder>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let order = order.into_inner();
+    if !order.is_valid() {
+        return Ok(bad_request("A purchase order needs a vendor and lines with a positive quantity and a unit cost"));
+    }
+    let user = users::Model::find_by_email(db, claims.subject()).await?;
+    match order.create(db, user.map(|user| user.id)).await {
+        Ok(detail) => Ok(HttpResponse::Ok().status(StatusCode::CREATED).insert_header(ContentType::json()).json(Response::from_data(detail))),
+        Err(e) => Ok(bad_request(&e.to_string())),
+    }
+}
+
+// the order with what each line was expected and received
+#[rustfmt::skip]
+#[get("/api/purchase_orders/{id}")]
+pub async fn show_purchase_order(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Clerk) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let Some(order) = purchase_orders::Entity::find_by_id(id.into_inner()).one(db).await? else {
+        return Ok(HttpResponse::NotFound().insert_header(ContentType::json()).json(Response::<String>::from_error("Purchase order not found")));
+    };
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(order.detail(db).await?)))
+}
+
+#[rustfmt::skip]
+#[post("/api/purchase_orders/{id}/order")]
+pub async fn place_purchase_order(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    Ok(detail_response(purchase_orders::Model::place(&db.0, id.into_inner()).await))
+}
+
+// adds what arrived to stock, a line can be received over several deliveries
+#[rustfmt::skip]
+#[post("/api/purchase_orders/{id}/receive")]
+pub async fn receive_purchase_order(db: web::Data<DB>, claims: Claims, id: web::Path<i32>, receipt: web::Json<PostReceipt>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    Ok(detail_response(purchase_orders::Model::receive(&db.0, id.into_inner(), &receipt).await))
+}
+
+// for an order the vendor won't send the rest of, what never arrived shows as discrepancies
+#[rustfmt::skip]
+#[post("/api/purchase_orders/{id}/close")]
+pub async fn close_purchase_order(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    Ok(detail_response(purchase_orders::Model::close_short(&db.0, id.into_inner()).await))
+}
@@ -0,0 +1,116 @@
+use crate::app_config::DB;
+use crate::middleware::auth::Claims;
+use crate::models::payroll::{PayrollOpts, PayrollReport};
+use crate::models::response::Response;
+use crate::models::sessions::{self, SessionLimits};
+use actix_web::http::header::{
+    self, ContentDisposition, ContentType, DispositionParam, DispositionType,
+};
+use actix_web::{get, web, HttpResponse};
+use chrono::{Datelike, Local, NaiveDate, NaiveTime};
+use entity::levels::Permission;
+use entity::{inventory_event, locations, residents, transaction_items};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+#[derive(Debug, Deserialize)]
+pub struct ValuationOpts {
+    /// value the stock as it was at the end of this day, defaults to now
+    pub as_of: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MarginOpts {
+    /// defaults to the first of this month
+    pub start: Option<NaiveDate>,
+    /// defaults to today
+    pub end: Option<NaiveDate>,
+    /// day, week or month (default)
+    pub interval: Option<String>,
+}
+
+fn attachment(filename: String) -> ContentDisposition {
+    ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![DispositionParam::Filename(filename)],
+    }
+}
+
+// hours worked at work locations over a pay period, as json, csv or pdf depending on ?format
+#[rustfmt::skip]
+#[get("/api/reports/payroll")]
+pub async fn payroll_report(db: web::Data<DB>, claims: Claims, query: web::Query<PayrollOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let opts = query.into_inner();
+    let period = match opts.pay_period() {
+        Ok(period) => period,
+        Err(e) => return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error(&e))),
+    };
+    let mut work_locations = locations::Entity::find().filter(locations::Column::IsWork.eq(true));
+    if let Some(location) = opts.location {
+        work_locations = work_locations.filter(locations::Column::Id.eq(location));
+    }
+    let locations: BTreeMap<i32, String> = work_locations.all(db).await?.into_iter().map(|loc| (loc.id, loc.name)).collect();
+
+    let range = sessions::lookup_range(period.start, period.end);
+    let sessions = sessions::load(db, range, None, &SessionLimits::default()).await?;
+    let names: BTreeMap<i32, String> = residents::Entity::find()
+        .filter(residents::Column::Doc.is_in(sessions.iter().map(|session| session.doc).collect::<Vec<_>>()))
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|resident| (resident.doc, resident.name))
+        .collect();
+    let report = PayrollReport::new(&opts, period, &sessions, &names, &locations);
+
+    match opts.format.as_deref() {
+        None | Some("json") => Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(report))),
+        Some("csv") => Ok(HttpResponse::Ok()
+            .insert_header((header::CONTENT_TYPE, "text/csv"))
+            .insert_header(attachment(report.filename("csv")))
+            .body(report.to_csv()?)),
+        Some("pdf") => Ok(HttpResponse::Ok()
+            .insert_header((header::CONTENT_TYPE, "application/pdf"))
+            .insert_header(attachment(report.filename("pdf")))
+            .body(report.to_pdf()?)),
+        Some(other) => Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error(&format!("Unknown report format: {other}")))),
+    }
+}
+
+// what the stock on hand was worth at a date, from the inventory history at the cost each unit came in at
+#[rustfmt::skip]
+#[get("/api/reports/inventory_valuation")]
+pub async fn inventory_valuation(db: web::Data<DB>, claims: Claims, query: web::Query<ValuationOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let as_of = query.as_of.map_or_else(|| Local::now().naive_local(), |date| date.and_time(NaiveTime::MIN) + chrono::Duration::days(1) - chrono::Duration::microseconds(1));
+    let valuation = inventory_event::valuation(&db.0, as_of).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(valuation)))
+}
+
+// revenue, cost of goods sold and margin per item and per day, week or month
+#[rustfmt::skip]
+#[get("/api/reports/gross_margin")]
+pub async fn gross_margin_report(db: web::Data<DB>, claims: Claims, query: web::Query<MarginOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let opts = query.into_inner();
+    let today = Local::now().date_naive();
+    let start = opts.start.unwrap_or_else(|| today.with_day(1).unwrap_or(today));
+    let end = opts.end.unwrap_or(today);
+    let interval = opts.interval.unwrap_or_else(|| "month".to_string());
+    if !["day", "week", "month"].contains(&interval.as_str()) {
+        return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error(&format!("Unknown interval: {interval}"))));
+    }
+    if end < start {
+        return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error("The period ends before it starts")));
+    }
+    let report = transaction_items::gross_margin(&db.0, start, end, &interval).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(report)))
+}
@@ -1,5 +1,7 @@
 use crate::app_config::DB;
+use crate::middleware::auth::Claims;
 use crate::models::response::{FilterOpts, ResidentHours, Response};
+use crate::models::sessions::{self, Session, SessionLimits};
 use actix_multipart::form::tempfile::TempFile;
 use actix_multipart::form::MultipartForm;
 use actix_web::{
@@ -7,15 +9,11 @@ use actix_web::{
     http::{header, StatusCode},
     patch, post, web, HttpResponse,
 };
-use chrono::NaiveDateTime;
+use entity::levels::Permission;
 use entity::prelude::UpdateResident;
-use entity::{
-    residents::{self, Entity as Resident},
-    timestamps,
-};
+use entity::residents::{self, Entity as Resident};
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, Set,
-    TryIntoModel,
+    ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, Set, TryIntoModel,
 };
 use std::path::PathBuf;
 
@@ -26,7 +24,10 @@ pub struct FormData {
 
 #[rustfmt::skip]
 #[post("/api/residents/{doc}/upload")]
-async fn upload_jpg(path: web::Path<String>,  form: MultipartForm<FormData>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+async fn upload_jpg(claims: Claims, path: web::Path<String>,  form: MultipartForm<FormData>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
     let default = std::env::var("UPLOAD_FILE_PATH").unwrap_or_default();
     let path = path.into_inner();
     let path = format!("{}.jpg", path);
@@ -60,7 +61,10 @@ async fn upload_jpg(path: web::Path<String>,  form: MultipartForm<FormData>) ->
 
 #[rustfmt::skip]
 #[get("/api/residents")]
-pub async fn index(db: web::Data<DB>,params: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+pub async fn index(db: web::Data<DB>, claims: Claims, params: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
     let db = &db.0;
     let params = params.into_inner();
     if let Some(true) = params.all {
@@ -83,7 +87,10 @@ pub async fn index(db: web::Data<DB>,params: web::Query<FilterOpts>) -> Result<H
 
 #[rustfmt::skip]
 #[get("/api/residents/{rfid}")]
-pub async fn show(db: web::Data<DB>, doc: actix_web::web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+pub async fn show(db: web::Data<DB>, claims: Claims, doc: actix_web::web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
     let db = &db.0;
     let doc = doc.into_inner();
     if let Ok(resident) = Resident::find().filter(residents::Column::IsDeleted.eq(false)).filter(residents::Column::Doc.eq(doc)).one(db).await {
@@ -103,36 +110,27 @@ pub async fn show(db: web::Data<DB>, doc: actix_web::web::Path<i32>) -> Result<H
 
 #[rustfmt::skip]
 #[get("/api/residents/{doc}/hours")]
-pub async fn get_resident_hours(db: web::Data<DB>, path: web::Path<i32>, query: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+pub async fn get_resident_hours(db: web::Data<DB>, claims: Claims, path: web::Path<i32>, query: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
     let db = &db.0;
     let resident_id = path.into_inner();
     let query_params = query.into_inner();
     let range = query_params.get_range().unwrap_or_default();
-    let target_location = query_params.location.unwrap();
-
-    // Retrieve all timestamps for the resident within the given period, ordered by timestamp
-    let timestamps = timestamps::Entity::find()
-        .filter(timestamps::Column::Doc.eq(resident_id))
-        .filter(timestamps::Column::Ts.between(range.0, range.1))
-        .order_by(timestamps::Column::Ts, sea_orm::Order::Asc)
-        .all(db)
-        .await?;
-
-    let mut total_duration_secs = 0i64;
-    let mut entry_ts: Option<NaiveDateTime> = None;
+    let Some(target_location) = query_params.location else {
+        let response = Response::<String>::from_error("A location is required to calculate hours");
+        return Ok(HttpResponse::BadRequest().insert_header(header::ContentType::json()).json(response));
+    };
 
-    for timestamp in timestamps.iter() {
-        if timestamp.location == target_location && entry_ts.is_some() {
-            let duration = timestamp.ts.signed_duration_since(entry_ts.unwrap());
-            total_duration_secs += duration.num_seconds();
-            log::debug!("Duration: {}", duration.num_seconds());
-            entry_ts = None;
-        } else if timestamp.location == target_location && entry_ts.is_none() {
-            entry_ts = Some(timestamp.ts);
-        } else {
-            continue;
-        }
-    }
+    // each scan into the location lasts until the resident's next scan anywhere
+    let range = (range.0.naive_utc(), range.1.naive_utc());
+    let sessions: Vec<Session> = sessions::load(db, range, Some(resident_id), &SessionLimits::default())
+        .await?
+        .into_iter()
+        .filter(|session| session.location == target_location)
+        .collect();
+    let total_duration_secs: i64 = sessions.iter().map(Session::seconds).sum();
     // Convert total seconds to hours
     let total_hours = total_duration_secs as f32 / 3600.0;
 
@@ -141,6 +139,8 @@ pub async fn get_resident_hours(db: web::Data<DB>, path: web::Path<i32>, query:
         resident_doc: resident_id,
         location: target_location,
         hours: total_hours,
+        flagged_sessions: sessions.iter().filter(|session| session.is_flagged()).count(),
+        adjusted_sessions: sessions.iter().filter(|session| session.adjusted).count(),
     };
     let response = Response::from_data(result);
     Ok(HttpResponse::Ok().insert_header(header::ContentType::json()).json(response))
@@ -148,7 +148,10 @@ pub async fn get_resident_hours(db: web::Data<DB>, path: web::Path<i32>, query:
 
 #[rustfmt::skip]
 #[post("/api/residents")]
-pub async fn store(db: web::Data<DB>, resident: web::Json<UpdateResident>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+pub async fn store(db: web::Data<DB>, claims: Claims, resident: web::Json<UpdateResident>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
     let db = &db.0;
     let resident = resident.into_inner();
     if let Ok(resident) = resident.into_active_model()?.save(db).await {
@@ -160,7 +163,10 @@ pub async fn store(db: web::Data<DB>, resident: web::Json<UpdateResident>) -> Re
 
 #[rustfmt::skip]
 #[delete("/api/residents/{rfid}")]
-pub async fn destroy(db: web::Data<DB>, rfid: web::Path<String>,) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+pub async fn destroy(db: web::Data<DB>, claims: Claims, rfid: web::Path<String>,) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Admin) {
+        return Ok(response);
+    }
     let db = &db.0;
     let rfid = rfid.into_inner();
     if let Ok(resident) = Resident::find().filter(residents::Column::Rfid.eq(rfid.clone())).one(db).await {
@@ -175,7 +181,10 @@ pub async fn destroy(db: web::Data<DB>, rfid: web::Path<String>,) -> Result<Http
 
 #[rustfmt::skip]
 #[patch("/api/residents/{rfid}")]
-pub async fn update(db: web::Data<DB>, rfid: actix_web::web::Path<String>, resident: web::Json<UpdateResident>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+pub async fn update(db: web::Data<DB>, claims: Claims, rfid: actix_web::web::Path<String>, resident: web::Json<UpdateResident>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
      let db = &db.0;
     let rfid = rfid.into_inner();
     let resident = resident.into_inner();
@@ -0,0 +1,91 @@
+use crate::app_config::DB;
+use crate::middleware::auth::Claims;
+use crate::models::response::{FilterOpts, Response};
+use actix_web::http::header::ContentType;
+use actix_web::{get, post, web, HttpResponse};
+use entity::levels::Permission;
+use entity::stock_counts::{self, CountedItem, PostCount, PostStockCount, StockScanOutcome};
+use entity::users;
+use reqwest::StatusCode;
+use sea_orm::{EntityTrait, PaginatorTrait, QueryOrder};
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound()
+        .insert_header(ContentType::json())
+        .json(Response::<String>::from_error("Stock count not found"))
+}
+
+// past counts, newest first
+#[rustfmt::skip]
+#[get("/api/stock_counts")]
+pub async fn index_stock_counts(db: web::Data<DB>, claims: Claims, query: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Clerk) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let params = query.into_inner();
+    let paginator = stock_counts::Entity::find().order_by_desc(stock_counts::Column::StartedAt).paginate(db, params.per_page.unwrap_or(10));
+    let page = paginator.fetch_page(params.page.unwrap_or(1).saturating_sub(1)).await?;
+    let total = paginator.num_items_and_pages().await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_paginator(&total, page)))
+}
+
+#[rustfmt::skip]
+#[post("/api/stock_counts")]
+pub async fn start_stock_count(db: web::Data<DB>, claims: Claims, count: web::Json<PostStockCount>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Clerk) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let user = users::Model::find_by_email(db, claims.subject()).await?;
+    let report = count.into_inner().start(db, user.map(|user| user.id)).await?.report(db).await?;
+    Ok(HttpResponse::Ok().status(StatusCode::CREATED).insert_header(ContentType::json()).json(Response::from_data(report)))
+}
+
+// what's been counted so far against the stock on hand
+#[rustfmt::skip]
+#[get("/api/stock_counts/{id}")]
+pub async fn show_stock_count(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Clerk) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let Some(count) = stock_counts::Entity::find_by_id(id.into_inner()).one(db).await? else {
+        return Ok(not_found());
+    };
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(count.report(db).await?)))
+}
+
+// a barcode scan counts one of the item, or send a quantity to enter the count by hand
+#[rustfmt::skip]
+#[post("/api/stock_counts/{id}/count")]
+pub async fn count_stock(db: web::Data<DB>, claims: Claims, id: web::Path<i32>, counted: web::Json<CountedItem>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Clerk) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    if counted.quantity.is_some_and(|quantity| quantity < 0) {
+        return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error("A counted quantity can't be negative")));
+    }
+    let Some(count) = stock_counts::Entity::find_by_id(id.into_inner()).one(db).await? else {
+        return Ok(not_found());
+    };
+    match count.count(db, &counted).await? {
+        StockScanOutcome::Counted(entry) => Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(entry))),
+        StockScanOutcome::Posted => Ok(HttpResponse::Conflict().insert_header(ContentType::json()).json(Response::<String>::from_error("Stock count was already posted"))),
+        StockScanOutcome::NotFound => Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error("Item not found"))),
+    }
+}
+
+// sets the counted items' stock to the count, recording the variances as adjustments
+#[rustfmt::skip]
+#[post("/api/stock_counts/{id}/post")]
+pub async fn post_stock_count(db: web::Data<DB>, claims: Claims, id: web::Path<i32>, posting: web::Json<PostCount>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    match stock_counts::Model::post(&db.0, id.into_inner(), &posting).await {
+        Ok(report) => Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(report))),
+        Err(e) => Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(Response::<String>::from_error(&e.to_string()))),
+    }
+}
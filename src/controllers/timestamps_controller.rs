@@ -1,25 +1,41 @@
 use crate::{
     app_config::DB,
-    models::response::{FilterOpts, Response, SortOrder},
+    events::{ScanEvent, ScanHub},
+    middleware::{auth::Claims, device::ScanDevice},
+    models::{
+        response::{FilterOpts, Response, SortOrder},
+        sessions::{self, SessionOpts},
+    },
 };
-use actix_web::{get, http::header::ContentType, post, web, HttpResponse};
+use actix_web::{get, http::header::ContentType, patch, post, web, HttpResponse};
 use entity::{
+    levels::Permission,
+    locations,
     residents::{self, Entity as Resident},
-    timestamps::{self, Entity as Timestamp, PostTimestamp, ResidentTimestamp},
+    timestamp_adjustments::{self, EditCorrection, InsertCorrection, VoidCorrection},
+    timestamps::{
+        self, BatchScanResult, DeviceScan, Entity as Timestamp, PostTimestamp, ResidentTimestamp,
+        ScanOutcome,
+    },
+    users,
 };
 use reqwest::StatusCode;
-use sea_orm::{
-    ActiveModelTrait, ColumnTrait, EntityTrait, IntoActiveModel, PaginatorTrait, QueryFilter,
-    QueryOrder, Set,
-};
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder};
+
+// most scans a device can upload in one request after reconnecting
+const MAX_BATCH_SIZE: usize = 1000;
 
 #[rustfmt::skip]
 #[get("/api/timestamps")]
-pub async fn index_timestamps(db: web::Data<DB>, query_params: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+pub async fn index_timestamps(db: web::Data<DB>, claims: Claims, query_params: web::Query<FilterOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
     let db = &db.0;
     let query_params = query_params.into_inner();
     let mut query = Timestamp::find()
-        .find_also_related(Resident).filter(residents::Column::IsDeleted.eq(false));
+        .find_also_related(Resident).filter(residents::Column::IsDeleted.eq(false))
+        .filter(timestamps::Column::IsVoided.eq(false));
     if let Some(location) = query_params.location {
         query = query.filter(timestamps::Column::Location.eq(location));
     }
@@ -49,42 +65,242 @@ pub async fn index_timestamps(db: web::Data<DB>, query_params: web::Query<Filter
         }
           }).collect();
           let response = Response::<ResidentTimestamp>::from_paginator(&total, response);
-          return Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(response))
+          Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(response))
+}
+
+// scans paired into sessions, with the ones staff should review before hours are finalized flagged
+#[rustfmt::skip]
+#[get("/api/sessions")]
+pub async fn index_sessions(db: web::Data<DB>, claims: Claims, query: web::Query<SessionOpts>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let opts = query.into_inner();
+    let today = chrono::Local::now().date_naive();
+    let end = opts.end.unwrap_or(today);
+    let start = opts.start.unwrap_or(end - chrono::Duration::days(7));
+    let sessions = sessions::load(db, sessions::lookup_range(start, end), opts.doc, &opts.limits())
+        .await?
+        .into_iter()
+        .filter(|session| session.entry.date() >= start && session.entry.date() <= end)
+        .filter(|session| opts.location.is_none_or(|location| session.location == location))
+        .filter(|session| !opts.flagged.unwrap_or(false) || session.is_flagged())
+        .collect();
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_vec(sessions)))
+}
+
+fn bad_request(msg: &str) -> HttpResponse {
+    HttpResponse::BadRequest()
+        .insert_header(ContentType::json())
+        .json(Response::<String>::from_error(msg))
+}
+
+// enter a scan that was missed, e.g. a resident who walked past the reader
+#[rustfmt::skip]
+#[post("/api/timestamps/corrections")]
+pub async fn insert_correction(db: web::Data<DB>, claims: Claims, correction: web::Json<InsertCorrection>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let correction = correction.into_inner();
+    if correction.reason.trim().is_empty() {
+        return Ok(bad_request("A reason is required"));
+    }
+    let Some(user) = users::Model::find_by_email(db, claims.subject()).await? else {
+        return Ok(bad_request("User not found"));
+    };
+    if Resident::find().filter(residents::Column::Doc.eq(correction.doc)).one(db).await?.is_none() {
+        return Ok(bad_request("Resident not found"));
+    }
+    if locations::Entity::find_by_id(correction.location).one(db).await?.is_none() {
+        return Ok(bad_request("Location not found"));
+    }
+    let timestamp = correction.apply(db, user.id).await?;
+    Ok(HttpResponse::Ok().status(StatusCode::CREATED).insert_header(ContentType::json()).json(Response::from_data(timestamp)))
+}
+
+#[rustfmt::skip]
+#[patch("/api/timestamps/{id}")]
+pub async fn edit_timestamp(db: web::Data<DB>, claims: Claims, id: web::Path<i32>, correction: web::Json<EditCorrection>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let correction = correction.into_inner();
+    if correction.reason.trim().is_empty() {
+        return Ok(bad_request("A reason is required"));
+    }
+    let Some(user) = users::Model::find_by_email(db, claims.subject()).await? else {
+        return Ok(bad_request("User not found"));
+    };
+    if let Some(location) = correction.location {
+        if locations::Entity::find_by_id(location).one(db).await?.is_none() {
+            return Ok(bad_request("Location not found"));
+        }
+    }
+    match Timestamp::find_by_id(id.into_inner()).one(db).await? {
+        Some(timestamp) if !timestamp.is_voided => {
+            let timestamp = correction.apply(db, timestamp, user.id).await?;
+            Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(timestamp)))
+        }
+        Some(_) => Ok(bad_request("Timestamp has been voided")),
+        None => Ok(HttpResponse::NotFound().insert_header(ContentType::json()).json(Response::<String>::from_error("Timestamp not found"))),
+    }
+}
+
+#[rustfmt::skip]
+#[post("/api/timestamps/{id}/void")]
+pub async fn void_timestamp(db: web::Data<DB>, claims: Claims, id: web::Path<i32>, correction: web::Json<VoidCorrection>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let correction = correction.into_inner();
+    if correction.reason.trim().is_empty() {
+        return Ok(bad_request("A reason is required"));
+    }
+    let Some(user) = users::Model::find_by_email(db, claims.subject()).await? else {
+        return Ok(bad_request("User not found"));
+    };
+    match Timestamp::find_by_id(id.into_inner()).one(db).await? {
+        Some(timestamp) if !timestamp.is_voided => {
+            let timestamp = correction.apply(db, timestamp, user.id).await?;
+            Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(timestamp)))
+        }
+        Some(_) => Ok(bad_request("Timestamp has already been voided")),
+        None => Ok(HttpResponse::NotFound().insert_header(ContentType::json()).json(Response::<String>::from_error("Timestamp not found"))),
+    }
+}
+
+// every manual change made to a timestamp, oldest first
+#[rustfmt::skip]
+#[get("/api/timestamps/{id}/adjustments")]
+pub async fn show_timestamp_adjustments(db: web::Data<DB>, claims: Claims, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Supervisor) {
+        return Ok(response);
+    }
+    let adjustments = timestamp_adjustments::Model::for_timestamp(&db.0, id.into_inner()).await?;
+    Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_vec(adjustments)))
 }
 
 #[rustfmt::skip]
 #[post("/api/timestamps")]
-pub async fn store_timestamp(db: web::Data<DB>, timestamp_data: web::Json<PostTimestamp>) -> Result<HttpResponse, Box<dyn std::error::Error>>{
+pub async fn store_timestamp(db: web::Data<DB>, claims: Claims, hub: web::Data<ScanHub>, timestamp_data: web::Json<PostTimestamp>) -> Result<HttpResponse, Box<dyn std::error::Error>>{
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let timestamp = timestamp_data.into_inner();
+    let outcome = timestamp.record(db, None).await?;
+    Ok(scan_response(&hub, &outcome))
+}
+
+// scans from a registered device are always recorded at the location the device is bound to
+#[rustfmt::skip]
+#[post("/api/devices/scan")]
+pub async fn store_device_timestamp(db: web::Data<DB>, hub: web::Data<ScanHub>, device: ScanDevice, scan: web::Json<DeviceScan>) -> Result<HttpResponse, Box<dyn std::error::Error>>{
     let db = &db.0;
-    let mut timestamp = timestamp_data.into_inner();
-    match Resident::find().filter(residents::Column::Rfid.eq(timestamp.rfid)).filter(residents::Column::IsDeleted.eq(false)).one(db).await? {
-        Some(resident) => {
-             let mut resident = resident.into_active_model();
-                if timestamp.location == resident.current_location.to_owned().unwrap() {
-                    resident.current_location = Set(0);
-                    timestamp.location = 0;
-                } else {
-                    resident.current_location = Set(timestamp.location);
-                }
-                let updated_resident = resident.save(db).await?;
-                let new_timestamp: timestamps::ActiveModel = timestamps::ActiveModel {
-                        doc: Set(updated_resident.doc.to_owned().unwrap()),
-                        location: Set(updated_resident.current_location.to_owned().unwrap()),
-                    ..Default::default()
-                };
-                let new_ts = new_timestamp.save(db).await?;
-                let response = Response::<ResidentTimestamp>::from_data(ResidentTimestamp {
-                    id: updated_resident.id.to_owned().unwrap(),
-                    name: updated_resident.name.to_owned().unwrap(),
-                    doc: updated_resident.doc.to_owned().unwrap(),
-                    location: new_ts.location.unwrap(),
-                    ts: new_ts.ts.unwrap(),
-                });
-                Ok(HttpResponse::Ok().content_type(ContentType::json()).status(StatusCode::CREATED).json(response))
+    let ScanDevice(device) = device;
+    let timestamp = scan.into_inner().at_location(device.location);
+    let outcome = timestamp.record(db, Some(device.id)).await?;
+    Ok(scan_response(&hub, &outcome))
+}
+
+// upload scans buffered while offline, read from the raw body because a batch is larger than the json limit
+#[rustfmt::skip]
+#[post("/api/timestamps/batch")]
+pub async fn store_timestamp_batch(db: web::Data<DB>, claims: Claims, hub: web::Data<ScanHub>, body: web::Bytes) -> Result<HttpResponse, Box<dyn std::error::Error>>{
+    if let Some(response) = claims.require(Permission::Officer) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let scans: Vec<PostTimestamp> = match serde_json::from_slice(&body) {
+        Ok(scans) => scans,
+        Err(e) => return Ok(batch_error(&e.to_string())),
+    };
+    if scans.len() > MAX_BATCH_SIZE {
+        return Ok(batch_error(&format!("A batch can hold at most {MAX_BATCH_SIZE} scans")));
+    }
+    let outcomes = PostTimestamp::record_batch(&scans, db, None).await?;
+    Ok(batch_response(&hub, &scans, &outcomes))
+}
+
+#[rustfmt::skip]
+#[post("/api/devices/scan/batch")]
+pub async fn store_device_timestamp_batch(db: web::Data<DB>, hub: web::Data<ScanHub>, device: ScanDevice, body: web::Bytes) -> Result<HttpResponse, Box<dyn std::error::Error>>{
+    let db = &db.0;
+    let ScanDevice(device) = device;
+    let scans: Vec<DeviceScan> = match serde_json::from_slice(&body) {
+        Ok(scans) => scans,
+        Err(e) => return Ok(batch_error(&e.to_string())),
+    };
+    if scans.len() > MAX_BATCH_SIZE {
+        return Ok(batch_error(&format!("A batch can hold at most {MAX_BATCH_SIZE} scans")));
+    }
+    let scans: Vec<PostTimestamp> = scans.into_iter().map(|scan| scan.at_location(device.location)).collect();
+    let outcomes = PostTimestamp::record_batch(&scans, db, Some(device.id)).await?;
+    Ok(batch_response(&hub, &scans, &outcomes))
+}
+
+fn scan_response(hub: &ScanHub, outcome: &ScanOutcome) -> HttpResponse {
+    match outcome {
+        ScanOutcome::Recorded(scan) => {
+            hub.publish(ScanEvent::from(scan));
+            let mut response =
+                Response::<ResidentTimestamp>::from_data(ResidentTimestamp::from(scan));
+            if !scan.warnings.is_empty() {
+                response.message =
+                    format!("Scan recorded with warnings: {}", scan.warnings.join(", "));
+            }
+            HttpResponse::Ok()
+                .content_type(ContentType::json())
+                .status(StatusCode::CREATED)
+                .json(response)
         }
-        None => {
-            let error_resp: Response<String> = Response::from_error(&String::from("Error retrieving resident: Not found in system, please add Resident."));
-            Ok(HttpResponse::Ok().content_type(ContentType::json()).json(error_resp))
+        ScanOutcome::Duplicate(_) => HttpResponse::Ok()
+            .content_type(ContentType::json())
+            .json(Response::<String>::from_success("Scan already recorded")),
+        ScanOutcome::NotFound => {
+            let error_resp: Response<String> = Response::from_error(&String::from(
+                "Error retrieving resident: Not found in system, please add Resident.",
+            ));
+            HttpResponse::Ok()
+                .content_type(ContentType::json())
+                .json(error_resp)
         }
+        ScanOutcome::Rejected(reason) => HttpResponse::Ok().content_type(ContentType::json()).json(
+            Response::<String>::from_error(&format!("Scan rejected: {reason}")),
+        ),
+        ScanOutcome::InFuture => HttpResponse::Ok().content_type(ContentType::json()).json(
+            Response::<String>::from_error("Scan time is ahead of the server clock"),
+        ),
     }
 }
+
+fn batch_response(
+    hub: &ScanHub,
+    scans: &[PostTimestamp],
+    outcomes: &[ScanOutcome],
+) -> HttpResponse {
+    let results: Vec<BatchScanResult> = scans
+        .iter()
+        .zip(outcomes)
+        .map(|(scan, outcome)| {
+            if let ScanOutcome::Recorded(result) = outcome {
+                hub.publish(ScanEvent::from(result));
+            }
+            BatchScanResult::new(scan, outcome)
+        })
+        .collect();
+    HttpResponse::Ok()
+        .content_type(ContentType::json())
+        .json(Response::from_vec(results))
+}
+
+fn batch_error(msg: &str) -> HttpResponse {
+    HttpResponse::BadRequest()
+        .content_type(ContentType::json())
+        .json(Response::<String>::from_error(msg))
+}
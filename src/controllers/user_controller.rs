@@ -2,24 +2,47 @@ use crate::app_config::DB;
 use crate::middleware::auth::Claims;
 use crate::models::response::Response;
 use actix_web::http::header::ContentType;
-use actix_web::{get, post, web, HttpResponse, Result};
-use sea_orm::{EntityTrait, IntoActiveValue};
+use actix_web::{delete, get, patch, post, web, HttpResponse, Result};
+use entity::levels::Permission;
+use sea_orm::{ActiveModelTrait, EntityTrait, IntoActiveModel, IntoActiveValue, Set};
 use serde::{Deserialize, Serialize};
 
 #[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
 pub struct PostUser {
     pub email: String,
     pub password: String,
+    pub level: Option<i32>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct PatchUser {
+    pub level: Option<i32>,
+    pub is_disabled: Option<bool>,
+}
+
+/// A temporary password the user has to change when they next log in
+#[derive(Deserialize, Serialize, Debug, Clone, Eq, PartialEq)]
+pub struct PasswordReset {
+    pub password: String,
+}
+
+fn user_not_found() -> HttpResponse {
+    HttpResponse::NotFound()
+        .insert_header(ContentType::json())
+        .json(Response::<String>::from_error("User not found"))
+}
+
+fn bad_request(msg: &str) -> HttpResponse {
+    HttpResponse::BadRequest()
+        .insert_header(ContentType::json())
+        .json(Response::<String>::from_error(msg))
 }
 
 #[rustfmt::skip]
 #[get("/api/users")]
 pub async fn get_users(claims: Claims, db: web::Data<DB>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    if !claims.is_valid() {
-        let response = Response::<String>::from_error("Unauthorized");
-        return Ok(HttpResponse::Unauthorized()
-            .insert_header(ContentType::json())
-            .json(response));
+    if let Some(response) = claims.require(Permission::Admin) {
+        return Ok(response);
     }
     let db = &db.0;
     let users = entity::users::Entity::find().all(db).await?;
@@ -27,22 +50,140 @@ pub async fn get_users(claims: Claims, db: web::Data<DB>) -> Result<HttpResponse
         Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(response))
 }
 
+#[rustfmt::skip]
+#[get("/api/levels")]
+pub async fn get_levels(claims: Claims, db: web::Data<DB>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Admin) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let levels = entity::levels::Entity::find().all(db).await?;
+    let response = Response::from_vec(levels);
+        Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(response))
+}
+
 #[rustfmt::skip]
 #[post("/api/users")]
 pub async fn create(claims: Claims, db: web::Data<DB>, user: web::Json<PostUser>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
-    if !claims.is_valid() {
-        let response = Response::<String>::from_error("Unauthorized");
-        return Ok(HttpResponse::Unauthorized()
-            .insert_header(ContentType::json())
-            .json(response));
+    if let Some(response) = claims.require(Permission::Admin) {
+        return Ok(response);
     }
     let db = &db.0;
-    let user = entity::users::ActiveModel {
+    let password = match entity::users::Model::hash_password(&user.password) {
+        Ok(password) => password,
+        Err(e) => return Ok(bad_request(&e)),
+    };
+    let mut new_user = entity::users::ActiveModel {
         email: user.email.clone().into_active_value(),
-        password: entity::users::Model::hash_password(&user.password).into_active_value(),
+        password: password.into_active_value(),
         ..Default::default()
     };
-    let _ = entity::users::Entity::insert(user).exec(db).await?;
+    if let Some(level) = user.level {
+        if entity::levels::Entity::find_by_id(level).one(db).await?.is_none() {
+            let response = Response::<String>::from_error("Level not found");
+            return Ok(HttpResponse::BadRequest().insert_header(ContentType::json()).json(response));
+        }
+        new_user.level = Set(level);
+    }
+    let _ = entity::users::Entity::insert(new_user).exec(db).await?;
         let response = Response::<String>::from_success("User created successfully");
         Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(response))
 }
+
+// logs a user out everywhere, e.g. when a token was stolen
+#[rustfmt::skip]
+#[delete("/api/users/{id}/sessions")]
+pub async fn revoke_sessions(claims: Claims, db: web::Data<DB>, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Admin) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let Some(user) = entity::users::Entity::find_by_id(id.into_inner()).one(db).await? else {
+        return Ok(user_not_found());
+    };
+    let revoked = entity::auth_sessions::Model::revoke_user(db, user.id, None).await?;
+    entity::auth_events::Model::record(db, entity::auth_events::SESSIONS_REVOKED, &user.email, Some(user.id), None).await?;
+    let response = Response::<String>::from_success(&format!("Revoked {revoked} sessions"));
+        Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(response))
+}
+
+// changes a user's level or disables them, either one logs them out so it applies right away
+#[rustfmt::skip]
+#[patch("/api/users/{id}")]
+pub async fn update(claims: Claims, db: web::Data<DB>, id: web::Path<i32>, patch: web::Json<PatchUser>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Admin) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let Some(user) = entity::users::Entity::find_by_id(id.into_inner()).one(db).await? else {
+        return Ok(user_not_found());
+    };
+    // an admin could otherwise lock everyone out by demoting or disabling themselves
+    if user.email == claims.subject() {
+        return Ok(bad_request("You can't change your own level or disable yourself"));
+    }
+    let mut active = user.clone().into_active_model();
+    if let Some(level) = patch.level {
+        if entity::levels::Entity::find_by_id(level).one(db).await?.is_none() {
+            return Ok(bad_request("Level not found"));
+        }
+        active.level = Set(level);
+    }
+    if let Some(is_disabled) = patch.is_disabled {
+        active.is_disabled = Set(is_disabled);
+    }
+    let updated = active.update(db).await?;
+    if updated.level != user.level || (updated.is_disabled && !user.is_disabled) {
+        entity::auth_sessions::Model::revoke_user(db, updated.id, None).await?;
+    }
+    if updated.is_disabled && !user.is_disabled {
+        entity::auth_events::Model::record(db, entity::auth_events::DISABLED, &updated.email, Some(updated.id), None).await?;
+    }
+        Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(Response::from_data(updated)))
+}
+
+#[rustfmt::skip]
+#[post("/api/users/{id}/password")]
+pub async fn reset_password(claims: Claims, db: web::Data<DB>, id: web::Path<i32>, reset: web::Json<PasswordReset>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Admin) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let Some(user) = entity::users::Entity::find_by_id(id.into_inner()).one(db).await? else {
+        return Ok(user_not_found());
+    };
+    let password = match entity::users::Model::hash_password(&reset.password) {
+        Ok(password) => password,
+        Err(e) => return Ok(bad_request(&e)),
+    };
+    let mut active = user.into_active_model();
+    active.password = Set(password);
+    active.must_change_password = Set(true);
+    let user = active.update(db).await?;
+    entity::auth_sessions::Model::revoke_user(db, user.id, None).await?;
+    entity::auth_events::Model::record(db, entity::auth_events::PASSWORD_RESET, &user.email, Some(user.id), None).await?;
+    let response = Response::<String>::from_success("Password reset, it must be changed at the next login");
+        Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(response))
+}
+
+// only for users with no history, anyone who has logged in, ordered stock, made adjustments or counts has to be disabled instead
+#[rustfmt::skip]
+#[delete("/api/users/{id}")]
+pub async fn destroy(claims: Claims, db: web::Data<DB>, id: web::Path<i32>) -> Result<HttpResponse, Box<dyn std::error::Error>> {
+    if let Some(response) = claims.require(Permission::Admin) {
+        return Ok(response);
+    }
+    let db = &db.0;
+    let Some(user) = entity::users::Entity::find_by_id(id.into_inner()).one(db).await? else {
+        return Ok(user_not_found());
+    };
+    if user.email == claims.subject() {
+        return Ok(bad_request("You can't delete yourself"));
+    }
+    if !entity::users::Model::delete_without_history(db, user.id).await? {
+        let response = Response::<String>::from_error("User has history that would be lost, disable them instead");
+        return Ok(HttpResponse::Conflict().insert_header(ContentType::json()).json(response));
+    }
+    let response = Response::<String>::from_success("User deleted");
+        Ok(HttpResponse::Ok().insert_header(ContentType::json()).json(response))
+}
@@ -0,0 +1,108 @@
+use chrono::NaiveDateTime;
+use entity::{prelude::OrmSerializable, timestamps::ScanResult};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+// events buffered per subscriber before a slow client starts missing them
+const HUB_CAPACITY: usize = 256;
+
+/// A resident moving between locations, published on every scan
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScanEvent {
+    pub id: i32,
+    pub doc: i32,
+    pub name: String,
+    pub from_location: i32,
+    pub to_location: i32,
+    pub ts: NaiveDateTime,
+}
+
+impl OrmSerializable for ScanEvent {}
+
+impl From<&ScanResult> for ScanEvent {
+    fn from(scan: &ScanResult) -> Self {
+        Self {
+            id: scan.resident.id,
+            doc: scan.resident.doc,
+            name: scan.resident.name.clone(),
+            from_location: scan.from_location,
+            to_location: scan.timestamp.location,
+            ts: scan.timestamp.ts,
+        }
+    }
+}
+
+impl ScanEvent {
+    /// Returns true if a board watching the given location needs to see this event
+    pub fn concerns(&self, location: i32) -> bool {
+        self.from_location == location || self.to_location == location
+    }
+}
+
+/// In-process broadcast hub that the scan endpoints publish to and the location boards subscribe to
+#[derive(Clone)]
+pub struct ScanHub(broadcast::Sender<ScanEvent>);
+
+impl Default for ScanHub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ScanHub {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(HUB_CAPACITY);
+        Self(sender)
+    }
+
+    pub fn publish(&self, event: ScanEvent) {
+        // an error only means nobody is listening right now
+        let _ = self.0.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ScanEvent> {
+        self.0.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(from_location: i32, to_location: i32) -> ScanEvent {
+        ScanEvent {
+            id: 1,
+            doc: 1,
+            name: "Test Resident".to_string(),
+            from_location,
+            to_location,
+            ts: "2024-03-04T08:00:00".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn concerns_both_ends_of_a_move() {
+        let scan = event(4, 5);
+        assert!(scan.concerns(4));
+        assert!(scan.concerns(5));
+        assert!(!scan.concerns(6));
+        assert!(!scan.concerns(0));
+    }
+
+    #[test]
+    fn concerns_signed_out_board() {
+        let scan = event(4, 0);
+        assert!(scan.concerns(0));
+        assert!(scan.concerns(4));
+        assert!(!scan.concerns(5));
+    }
+
+    #[test]
+    fn hub_delivers_to_subscribers() {
+        let hub = ScanHub::new();
+        let mut board = hub.subscribe();
+        hub.publish(event(4, 5));
+        let received = board.try_recv().unwrap();
+        assert!(received.concerns(5));
+    }
+}
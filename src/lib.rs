@@ -5,3 +5,7 @@ pub mod controllers;
 pub mod app_config;
 
 pub mod middleware;
+
+pub mod events;
+
+pub mod bootstrap;
@@ -17,10 +17,14 @@ use actix_web::{
 };
 use scan_mvcf::{
     app_config::DB,
+    bootstrap,
     controllers::{
-        accounts_controller, auth_controller, items_controller, locations_controller,
-        order_controller, residents_controller, timestamps_controller, user_controller,
+        accounts_controller, auth_controller, callouts_controller, devices_controller,
+        headcounts_controller, items_controller, locations_controller, movement_controller,
+        order_controller, purchase_orders_controller, reports_controller, residents_controller,
+        stock_counts_controller, timestamps_controller, user_controller,
     },
+    events::ScanHub,
     middleware::auth::SECRET_KEY,
 };
 use std::io;
@@ -30,6 +34,14 @@ async fn main() -> io::Result<()> {
     std::env::set_var("RUST_LOG", "debug");
     env_logger::init();
     dotenvy::dotenv().ok();
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("create-admin") {
+        if let Err(e) = bootstrap::create_admin(args.next()).await {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
     let upload_dir = std::env::var("UPLOAD_FILE_PATH");
     log::debug!(
         "Temp file path: {:?}",
@@ -49,6 +61,7 @@ async fn main() -> io::Result<()> {
     let tempfile_path = tempfile_path.directory(upload_dir.unwrap_or_default());
     if let Ok(db) = DB::get().await {
         log::info!("Connected to database");
+        let hub = ScanHub::new();
 
         HttpServer::new(move || {
             let cors = Cors::permissive()
@@ -60,6 +73,7 @@ async fn main() -> io::Result<()> {
 
             App::new()
                 .app_data(Data::new(db.clone()))
+                .app_data(Data::new(hub.clone()))
                 .app_data(Data::new(tempfile_path.clone()))
                 .app_data(json_config.clone())
                 .wrap(
@@ -72,8 +86,10 @@ async fn main() -> io::Result<()> {
                         .build(),
                 )
                 .service(locations_controller::index)
+                .service(locations_controller::occupancy)
                 .service(locations_controller::show)
                 .service(locations_controller::show_location_residents)
+                .service(locations_controller::location_events)
                 .service(locations_controller::store)
                 .service(locations_controller::update)
                 .service(locations_controller::destroy)
@@ -85,19 +101,81 @@ async fn main() -> io::Result<()> {
                 .service(residents_controller::upload_jpg)
                 .service(residents_controller::get_resident_hours)
                 .service(timestamps_controller::index_timestamps)
+                .service(timestamps_controller::index_sessions)
+                .service(timestamps_controller::insert_correction)
+                .service(timestamps_controller::edit_timestamp)
+                .service(timestamps_controller::void_timestamp)
+                .service(timestamps_controller::show_timestamp_adjustments)
                 .service(timestamps_controller::store_timestamp)
+                .service(timestamps_controller::store_timestamp_batch)
+                .service(timestamps_controller::store_device_timestamp)
+                .service(timestamps_controller::store_device_timestamp_batch)
+                .service(devices_controller::index_devices)
+                .service(devices_controller::register_device)
+                .service(devices_controller::revoke_device)
+                .service(reports_controller::payroll_report)
+                .service(reports_controller::inventory_valuation)
+                .service(reports_controller::gross_margin_report)
+                .service(movement_controller::index_movement_rules)
+                .service(movement_controller::store_movement_rule)
+                .service(movement_controller::destroy_movement_rule)
+                .service(movement_controller::index_restrictions)
+                .service(movement_controller::store_restriction)
+                .service(movement_controller::destroy_restriction)
+                .service(movement_controller::index_rejected_scans)
+                .service(callouts_controller::index_callouts)
+                .service(callouts_controller::overdue_callouts)
+                .service(callouts_controller::store_callout)
+                .service(callouts_controller::destroy_callout)
+                .service(headcounts_controller::index_headcounts)
+                .service(headcounts_controller::start_headcount)
+                .service(headcounts_controller::show_headcount)
+                .service(headcounts_controller::scan_headcount)
+                .service(headcounts_controller::close_headcount)
                 .service(auth_controller::login)
                 .service(auth_controller::logout)
+                .service(auth_controller::refresh)
+                .service(auth_controller::change_password)
+                .service(auth_controller::index_sessions)
+                .service(auth_controller::revoke_session)
+                .service(auth_controller::index_auth_events)
                 .service(accounts_controller::get_all_transactions)
+                .service(accounts_controller::void_transaction)
+                .service(accounts_controller::refund_transaction)
                 .service(accounts_controller::index_accounts)
+                .service(accounts_controller::reconcile_accounts)
                 .service(accounts_controller::show_account)
+                .service(accounts_controller::show_account_ledger)
                 .service(accounts_controller::post_transaction)
                 .service(accounts_controller::show_account_transactions)
+                .service(accounts_controller::index_purchase_limits)
+                .service(accounts_controller::store_purchase_limit)
+                .service(accounts_controller::destroy_purchase_limit)
+                .service(accounts_controller::show_allowance)
+                .service(purchase_orders_controller::index_purchase_orders)
+                .service(purchase_orders_controller::store_purchase_order)
+                .service(purchase_orders_controller::show_purchase_order)
+                .service(purchase_orders_controller::place_purchase_order)
+                .service(purchase_orders_controller::receive_purchase_order)
+                .service(purchase_orders_controller::close_purchase_order)
                 .service(order_controller::get_orders)
                 .service(user_controller::get_users)
                 .service(user_controller::create)
+                .service(user_controller::update)
+                .service(user_controller::destroy)
+                .service(user_controller::reset_password)
+                .service(user_controller::revoke_sessions)
+                .service(user_controller::get_levels)
                 .service(items_controller::index_items)
+                .service(items_controller::low_stock_items)
+                .service(items_controller::reorder_low_stock)
                 .service(items_controller::create_item)
+                .service(items_controller::update_item)
+                .service(stock_counts_controller::index_stock_counts)
+                .service(stock_counts_controller::start_stock_count)
+                .service(stock_counts_controller::show_stock_count)
+                .service(stock_counts_controller::count_stock)
+                .service(stock_counts_controller::post_stock_count)
                 .wrap(middleware::Logger::default())
                 .wrap(cors)
         })
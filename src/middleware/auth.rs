@@ -1,81 +1,189 @@
+use super::unauthorized;
+use crate::app_config::DB;
+use crate::models::response::Response;
 use actix_session::SessionExt;
-use actix_web::{Error, FromRequest};
+use actix_web::http::header::{ContentType, AUTHORIZATION};
+use actix_web::{web, Error, FromRequest, HttpResponse};
 use chrono::Days;
-use futures::future::{ok, Ready};
+use entity::{auth_sessions, users};
+use entity::levels::Permission;
+use futures::future::LocalBoxFuture;
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use std::sync::OnceLock;
 
 pub static SECRET_KEY: OnceLock<String> = OnceLock::new();
 
+/// How long a login lasts, and with it the cookie token and the refresh token
+pub const SESSION_DAYS: u64 = 7;
+/// Bearer access tokens are short lived, clients trade their refresh token for a new pair
+pub const ACCESS_TOKEN_MINUTES: i64 = 15;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
      sub: String,
      exp: usize,
+     #[serde(default)]
+     level: i32,
+     /// the `auth_sessions` login the token was issued for
+     #[serde(default)]
+     sid: String,
+     /// set only on refresh tokens, which are refused everywhere but `/api/auth/refresh`
+     #[serde(default, skip_serializing_if = "Option::is_none")]
+     refresh: Option<String>,
+     /// the password was reset by an admin, nothing but changing it is allowed
+     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+     reset: bool,
+}
+
+/// Returned on login and refresh for clients that can't keep a cookie
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub token_type: String,
+    /// seconds until the access token expires
+    pub expires_in: i64,
 }
+impl entity::prelude::OrmSerializable for TokenPair {}
+
 impl Claims {
+    /// Email of the logged in user
+    pub fn subject(&self) -> &str {
+        &self.sub
+    }
+
+    pub fn session_key(&self) -> &str {
+        &self.sid
+    }
+
+    /// The refresh token's id, none for access tokens
+    pub fn refresh_key(&self) -> Option<&str> {
+        self.refresh.as_deref()
+    }
+
     pub fn is_valid(&self) -> bool {
        self.exp > chrono::offset::Local::now().timestamp_millis() as usize
        }
-}
-impl Default for Claims {
-    fn default() -> Self {
-        Self {
-            sub: "".to_string(),
-            exp: 0,
+
+    /// Returns true if the token is valid and its level meets the required permission
+    pub fn has_permission(&self, permission: Permission) -> bool {
+        self.is_valid() && self.level >= i32::from(permission)
+    }
+
+    /// Returns the error response for a request lacking the required permission, if any
+    pub fn require(&self, permission: Permission) -> Option<HttpResponse> {
+        if !self.is_valid() {
+            Some(HttpResponse::Unauthorized()
+                .insert_header(ContentType::json())
+                .json(Response::<String>::from_error("Unauthorized")))
+        } else if self.reset {
+            Some(HttpResponse::Forbidden()
+                .insert_header(ContentType::json())
+                .json(Response::<String>::from_error("Password change required")))
+        } else if !self.has_permission(permission) {
+            Some(HttpResponse::Forbidden()
+                .insert_header(ContentType::json())
+                .json(Response::<String>::from_error("Insufficient permission")))
+        } else {
+            None
         }
     }
 }
-
+/// Only extracts for a logged in session with a valid token, anything else is refused with a 401 before the
+/// handler runs. Handlers then `require` the permission they need, which answers 403 for a level too low.
+/// Take an `Option<Claims>` where being logged in is optional.
+/// An `Authorization: Bearer` access token is taken over the session cookie, either way the login it
+/// belongs to must not have been revoked.
 impl FromRequest for Claims {
     type Error = Error;
-    type Future = Ready<Result<Claims, Error>>;
+    type Future = LocalBoxFuture<'static, Result<Claims, Error>>;
 
     #[rustfmt::skip]
     fn from_request(req: &actix_web::HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
-        let session = req.get_session();
-        if let Ok(Some(jwt)) = session.get::<String>("token") {
-            if let Some(token) = validate_jwt(&jwt) {
-                 ok(token)
-            } else {
-                ok(Claims::default())
+        let bearer = req.headers().get(AUTHORIZATION)
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_owned());
+        let token = bearer.or_else(|| req.get_session().get::<String>("token").ok().flatten());
+        let db = req.app_data::<web::Data<DB>>().cloned();
+        Box::pin(async move {
+            let (Some(jwt), Some(db)) = (token, db) else {
+                return Err(unauthorized("Unauthorized"));
+            };
+            let claims = match validate_jwt(&jwt) {
+                Some(claims) if claims.is_valid() && claims.refresh.is_none() && !claims.sid.is_empty() => claims,
+                _ => return Err(unauthorized("Session expired, please log in again")),
+            };
+            match auth_sessions::Model::find_active(&db.0, &claims.sid).await {
+                Ok(Some(_)) => Ok(claims),
+                Ok(None) => Err(unauthorized("Session expired, please log in again")),
+                Err(e) => Err(actix_web::error::ErrorInternalServerError(e)),
             }
-        } else {
-              ok(Claims::default())
-        } 
+        })
     }
 }
 
-pub fn create_jwt(sub: &str) -> String {
+fn sign(claims: &Claims) -> String {
     let secret = SECRET_KEY.get_or_init(|| std::env::var("JWT_SECRET_KEY").unwrap_or("secret".to_string())).clone();
-    let expiration = chrono::offset::Local::now().checked_add_days(Days::new(7)).unwrap();
-    let claims = Claims {
-        sub: sub.to_owned(),
-        exp: expiration.timestamp_millis() as usize,
-    };
-    let token = encode(
+    encode(
         &Header::default(),
-        &claims,
+        claims,
         &EncodingKey::from_secret(secret.as_bytes()),
     )
-    .unwrap();
-    token
+    .unwrap()
+}
+
+/// The token kept in the cookie session, it lasts as long as the login
+pub fn create_jwt(user: &users::Model, level: i32, session: &auth_sessions::Model) -> String {
+    let expiration = chrono::offset::Local::now().checked_add_days(Days::new(SESSION_DAYS)).unwrap();
+    sign(&Claims {
+        sub: user.email.clone(),
+        exp: expiration.timestamp_millis() as usize,
+        level,
+        sid: session.session_key.clone(),
+        refresh: None,
+        reset: user.must_change_password,
+    })
+}
+
+/// A short lived access token, and a refresh token good until the login expires
+pub fn create_token_pair(user: &users::Model, level: i32, session: &auth_sessions::Model) -> TokenPair {
+    let expiration = chrono::offset::Local::now() + chrono::Duration::minutes(ACCESS_TOKEN_MINUTES);
+    let access_token = sign(&Claims {
+        sub: user.email.clone(),
+        exp: expiration.timestamp_millis() as usize,
+        level,
+        sid: session.session_key.clone(),
+        refresh: None,
+        reset: user.must_change_password,
+    });
+    let refresh_token = sign(&Claims {
+        sub: user.email.clone(),
+        exp: session.expires_at.and_local_timezone(chrono::Local).earliest().map_or(0, |at| at.timestamp_millis()) as usize,
+        level,
+        sid: session.session_key.clone(),
+        refresh: Some(session.refresh_key.clone()),
+        reset: user.must_change_password,
+    });
+    TokenPair {
+        access_token,
+        refresh_token,
+        token_type: "Bearer".to_string(),
+        expires_in: ACCESS_TOKEN_MINUTES * 60,
+    }
 }
 impl Claims {
     pub fn update_jwt(&self) -> String {
-    let secret = SECRET_KEY.get_or_init(|| std::env::var("JWT_SECRET_KEY").unwrap_or("secret".to_string())).clone();
-    let expiration = chrono::offset::Local::now().checked_add_days(Days::new(7)).unwrap();
-        let claims = Claims {
+    let expiration = chrono::offset::Local::now().checked_add_days(Days::new(SESSION_DAYS)).unwrap();
+        sign(&Claims {
             sub: self.sub.to_owned(),
             exp: expiration.timestamp_millis() as usize,
-        };
-        let token = encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(secret.as_bytes()),
-        )
-        .unwrap();
-        token
+            level: self.level,
+            sid: self.sid.clone(),
+            refresh: None,
+            reset: self.reset,
+        })
     }
 }
 
@@ -0,0 +1,35 @@
+use super::unauthorized;
+use crate::app_config::DB;
+use actix_web::{web, Error, FromRequest, HttpRequest};
+use futures::future::LocalBoxFuture;
+
+/// Header carrying the api key issued to a scanner device on registration
+pub const DEVICE_KEY_HEADER: &str = "X-Device-Key";
+
+/// A registered, non-revoked scanner device identified by its api key
+#[derive(Debug)]
+pub struct ScanDevice(pub entity::devices::Model);
+
+impl FromRequest for ScanDevice {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut actix_web::dev::Payload) -> Self::Future {
+        let api_key = req
+            .headers()
+            .get(DEVICE_KEY_HEADER)
+            .and_then(|key| key.to_str().ok())
+            .map(str::to_owned);
+        let db = req.app_data::<web::Data<DB>>().cloned();
+        Box::pin(async move {
+            let (Some(api_key), Some(db)) = (api_key, db) else {
+                return Err(unauthorized("Missing device key"));
+            };
+            match entity::devices::Model::find_by_key(&db.0, &api_key).await {
+                Ok(Some(device)) => Ok(Self(device)),
+                Ok(None) => Err(unauthorized("Unknown or revoked device")),
+                Err(e) => Err(actix_web::error::ErrorInternalServerError(e)),
+            }
+        })
+    }
+}
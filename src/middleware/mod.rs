@@ -1 +1,37 @@
+use crate::models::response::Response;
+use actix_web::{error::InternalError, http::header::ContentType, Error, HttpRequest, HttpResponse};
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
 pub mod auth;
+pub mod device;
+
+/// Rejects a request before it reaches the handler, with the usual json error body
+pub(crate) fn unauthorized(msg: &str) -> Error {
+    let response = HttpResponse::Unauthorized()
+        .insert_header(ContentType::json())
+        .json(Response::<String>::from_error(msg));
+    InternalError::from_response(msg.to_string(), response).into()
+}
+
+/// Comma separated `TRUSTED_PROXIES` from `.env`, the only peers whose forwarded headers are believed
+static TRUSTED_PROXIES: OnceLock<Vec<IpAddr>> = OnceLock::new();
+
+/// The address the request came from. `X-Forwarded-For`/`Forwarded` are ignored unless the peer is one of
+/// the `TRUSTED_PROXIES`, otherwise any client could claim to be any ip.
+pub(crate) fn client_ip(req: &HttpRequest) -> Option<String> {
+    let peer = req.peer_addr()?.ip();
+    let trusted = TRUSTED_PROXIES.get_or_init(|| {
+        std::env::var("TRUSTED_PROXIES")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|proxy| proxy.trim().parse().ok())
+            .collect()
+    });
+    if trusted.contains(&peer) {
+        if let Some(forwarded) = req.connection_info().realip_remote_addr() {
+            return Some(forwarded.to_string());
+        }
+    }
+    Some(peer.to_string())
+}
@@ -1 +1,3 @@
 pub mod response;
+pub mod payroll;
+pub mod sessions;
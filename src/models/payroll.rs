@@ -0,0 +1,500 @@
+use super::sessions::Session;
+use chrono::{Datelike, Days, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use entity::prelude::OrmSerializable;
+use printpdf::{BuiltinFont, Mm, PdfDocument};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const DEFAULT_WEEKLY_OVERTIME: f32 = 40.0;
+
+#[derive(Debug, Deserialize)]
+pub struct PayrollOpts {
+    // query string parameters
+    /// weekly (default), biweekly or custom
+    pub period: Option<String>,
+    /// first day of the period, weekly and biweekly periods default to starting on the current week's monday
+    pub start: Option<NaiveDate>,
+    /// last day of a custom period
+    pub end: Option<NaiveDate>,
+    pub location: Option<i32>,
+    /// round every scan to the nearest number of minutes, e.g. 15
+    pub round_to: Option<i64>,
+    /// hours worked in one day before the rest is overtime
+    pub daily_overtime: Option<f32>,
+    /// hours worked in one week before the rest is overtime, defaults to 40
+    pub weekly_overtime: Option<f32>,
+    /// json (default), csv or pdf
+    pub format: Option<String>,
+}
+
+/// Inclusive range of days covered by a payroll report
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PayPeriod {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl PayPeriod {
+    pub fn first_moment(&self) -> NaiveDateTime {
+        self.start.and_time(NaiveTime::MIN)
+    }
+    pub fn last_moment(&self) -> NaiveDateTime {
+        self.end.and_time(NaiveTime::MIN) + Duration::days(1)
+    }
+}
+
+impl PayrollOpts {
+    pub fn pay_period(&self) -> Result<PayPeriod, String> {
+        let today = Local::now().date_naive();
+        let monday = today - Days::new(u64::from(today.weekday().num_days_from_monday()));
+        let (start, end) = match self.period.as_deref() {
+            None | Some("weekly") => {
+                let start = self.start.unwrap_or(monday);
+                (start, start + Days::new(6))
+            }
+            Some("biweekly") => {
+                let start = self.start.unwrap_or(monday - Days::new(7));
+                (start, start + Days::new(13))
+            }
+            Some("custom") => match (self.start, self.end) {
+                (Some(start), Some(end)) => (start, end),
+                _ => return Err("A custom period needs both a start and an end".to_string()),
+            },
+            Some(other) => return Err(format!("Unknown pay period: {other}")),
+        };
+        if end < start {
+            return Err("The period ends before it starts".to_string());
+        }
+        Ok(PayPeriod { start, end })
+    }
+
+    /// Rounds a scan to the nearest `round_to` minutes of its day
+    fn round(&self, ts: NaiveDateTime) -> NaiveDateTime {
+        match self.round_to {
+            Some(minutes) if minutes > 0 => {
+                let increment = minutes * 60;
+                let seconds = i64::from(ts.num_seconds_from_midnight());
+                let rounded = (seconds + increment / 2) / increment * increment;
+                ts.date().and_time(NaiveTime::MIN) + Duration::seconds(rounded)
+            }
+            _ => ts,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayrollDay {
+    pub date: NaiveDate,
+    pub hours: f32,
+    pub regular_hours: f32,
+    pub overtime_hours: f32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ResidentPayroll {
+    pub doc: i32,
+    pub name: String,
+    pub location: i32,
+    pub location_name: String,
+    pub days: Vec<PayrollDay>,
+    pub total_hours: f32,
+    pub regular_hours: f32,
+    pub overtime_hours: f32,
+    /// sessions that need reviewing before these hours are final
+    pub flagged_sessions: usize,
+    /// sessions with a scan corrected by a supervisor
+    pub adjusted_sessions: usize,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PayrollReport {
+    pub period: PayPeriod,
+    pub residents: Vec<ResidentPayroll>,
+}
+
+impl OrmSerializable for PayrollReport {}
+
+fn hours(seconds: i64) -> f32 {
+    (seconds as f64 / 3600.0) as f32
+}
+
+impl PayrollReport {
+    /// Builds the report from the sessions at the given work locations, keyed by location id with their names.
+    /// Open sessions are left out, a session running past midnight counts towards both days.
+    pub fn new(
+        opts: &PayrollOpts,
+        period: PayPeriod,
+        sessions: &[Session],
+        names: &BTreeMap<i32, String>,
+        locations: &BTreeMap<i32, String>,
+    ) -> Self {
+        // seconds worked per (resident, location) per day
+        let mut worked: BTreeMap<(i32, i32), BTreeMap<NaiveDate, i64>> = BTreeMap::new();
+        let mut flagged: BTreeMap<(i32, i32), usize> = BTreeMap::new();
+        let mut adjusted: BTreeMap<(i32, i32), usize> = BTreeMap::new();
+        for session in sessions {
+            if !locations.contains_key(&session.location) {
+                continue;
+            }
+            let key = (session.doc, session.location);
+            let in_period = session.entry < period.last_moment()
+                && session.exit.is_none_or(|exit| exit > period.first_moment());
+            if session.is_flagged() && in_period {
+                // listed even without hours so an open session isn't missed
+                worked.entry(key).or_default();
+                *flagged.entry(key).or_default() += 1;
+            }
+            if session.adjusted && in_period {
+                *adjusted.entry(key).or_default() += 1;
+            }
+            let Some(exit) = session.exit else { continue };
+            // clock in and out are rounded before the session is split at midnight
+            let mut from = opts.round(session.entry).max(period.first_moment());
+            let until = opts.round(exit).min(period.last_moment());
+            while from < until {
+                let midnight = from.date().and_time(NaiveTime::MIN) + Duration::days(1);
+                let to = midnight.min(until);
+                let part = to.signed_duration_since(from).num_seconds();
+                *worked
+                    .entry(key)
+                    .or_default()
+                    .entry(from.date())
+                    .or_default() += part;
+                from = to;
+            }
+        }
+        let daily_limit = opts.daily_overtime.map(|limit| (limit * 3600.0) as i64);
+        let weekly_limit =
+            (opts.weekly_overtime.unwrap_or(DEFAULT_WEEKLY_OVERTIME) * 3600.0) as i64;
+        // the limits apply to everything a resident worked that day or week, whichever locations it was at
+        let mut daily: BTreeMap<(i32, NaiveDate), i64> = BTreeMap::new();
+        for ((doc, _), days) in &worked {
+            for (date, seconds) in days {
+                *daily.entry((*doc, *date)).or_default() += seconds;
+            }
+        }
+        // worked and regular seconds per resident per day
+        let mut regular: BTreeMap<(i32, NaiveDate), (i64, i64)> = BTreeMap::new();
+        let mut week_regular: BTreeMap<(i32, NaiveDate), i64> = BTreeMap::new();
+        for ((doc, date), seconds) in daily {
+            let daily_overtime = daily_limit.map_or(0, |limit| (seconds - limit).max(0));
+            // weeks run from monday like the pay periods, wherever the period starts
+            let monday = date - Days::new(u64::from(date.weekday().num_days_from_monday()));
+            let regular_so_far = week_regular.entry((doc, monday)).or_default();
+            let day_regular =
+                (seconds - daily_overtime).min((weekly_limit - *regular_so_far).max(0));
+            *regular_so_far += day_regular;
+            regular.insert((doc, date), (seconds, day_regular));
+        }
+        let residents = worked
+            .into_iter()
+            .map(|((doc, location), days)| {
+                let days: Vec<PayrollDay> = days
+                    .into_iter()
+                    .map(|(date, seconds)| {
+                        // a day's regular hours are shared out by the time spent at each location
+                        let regular = match regular.get(&(doc, date)) {
+                            Some(&(total, day_regular)) if total > 0 => {
+                                day_regular * seconds / total
+                            }
+                            _ => 0,
+                        };
+                        PayrollDay {
+                            date,
+                            hours: hours(seconds),
+                            regular_hours: hours(regular),
+                            overtime_hours: hours(seconds - regular),
+                        }
+                    })
+                    .collect();
+                ResidentPayroll {
+                    doc,
+                    name: names.get(&doc).cloned().unwrap_or_default(),
+                    location,
+                    location_name: locations.get(&location).cloned().unwrap_or_default(),
+                    total_hours: days.iter().map(|day| day.hours).sum(),
+                    regular_hours: days.iter().map(|day| day.regular_hours).sum(),
+                    overtime_hours: days.iter().map(|day| day.overtime_hours).sum(),
+                    flagged_sessions: flagged.get(&(doc, location)).copied().unwrap_or_default(),
+                    adjusted_sessions: adjusted.get(&(doc, location)).copied().unwrap_or_default(),
+                    days,
+                }
+            })
+            .collect();
+        Self { period, residents }
+    }
+
+    pub fn filename(&self, extension: &str) -> String {
+        format!(
+            "payroll_{}_{}.{extension}",
+            self.period.start, self.period.end
+        )
+    }
+
+    /// One row per resident, location and day worked
+    pub fn to_csv(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer.write_record([
+            "doc",
+            "name",
+            "location",
+            "date",
+            "hours",
+            "regular_hours",
+            "overtime_hours",
+        ])?;
+        for resident in &self.residents {
+            for day in &resident.days {
+                writer.write_record([
+                    resident.doc.to_string(),
+                    resident.name.clone(),
+                    resident.location_name.clone(),
+                    day.date.to_string(),
+                    format!("{:.2}", day.hours),
+                    format!("{:.2}", day.regular_hours),
+                    format!("{:.2}", day.overtime_hours),
+                ])?;
+            }
+        }
+        Ok(writer.into_inner()?)
+    }
+
+    /// Printable A4 report with a block of daily hours and totals for every resident
+    pub fn to_pdf(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        const TOP: f32 = 280.0;
+        const BOTTOM: f32 = 20.0;
+        const LINE: f32 = 6.0;
+        let title = format!("Payroll {} - {}", self.period.start, self.period.end);
+        let (doc, page, layer) = PdfDocument::new(&title, Mm(210.0), Mm(297.0), "report");
+        let font = doc.add_builtin_font(BuiltinFont::Helvetica)?;
+        let bold = doc.add_builtin_font(BuiltinFont::HelveticaBold)?;
+        let mut layer = doc.get_page(page).get_layer(layer);
+        let mut y = TOP;
+        layer.use_text(&title, 16.0, Mm(15.0), Mm(y), &bold);
+        y -= LINE * 2.0;
+        for resident in &self.residents {
+            // keep a resident's heading on the same page as at least its first day
+            if y - LINE * 3.0 < BOTTOM {
+                let (page, next) = doc.add_page(Mm(210.0), Mm(297.0), "report");
+                layer = doc.get_page(page).get_layer(next);
+                y = TOP;
+            }
+            let mut heading = format!(
+                "{} ({}) - {}",
+                resident.name, resident.doc, resident.location_name
+            );
+            if resident.flagged_sessions > 0 {
+                heading.push_str(&format!(
+                    " - {} flagged sessions",
+                    resident.flagged_sessions
+                ));
+            }
+            if resident.adjusted_sessions > 0 {
+                heading.push_str(&format!(
+                    " - {} adjusted sessions",
+                    resident.adjusted_sessions
+                ));
+            }
+            layer.use_text(heading, 12.0, Mm(15.0), Mm(y), &bold);
+            y -= LINE;
+            let rows = resident
+                .days
+                .iter()
+                .map(|day| {
+                    (
+                        day.date.to_string(),
+                        day.hours,
+                        day.regular_hours,
+                        day.overtime_hours,
+                        &font,
+                    )
+                })
+                .chain(std::iter::once((
+                    "Total".to_string(),
+                    resident.total_hours,
+                    resident.regular_hours,
+                    resident.overtime_hours,
+                    &bold,
+                )));
+            for (label, total, regular, overtime, font) in rows {
+                if y < BOTTOM {
+                    let (page, next) = doc.add_page(Mm(210.0), Mm(297.0), "report");
+                    layer = doc.get_page(page).get_layer(next);
+                    y = TOP;
+                }
+                layer.use_text(label, 10.0, Mm(20.0), Mm(y), font);
+                layer.use_text(format!("{total:.2} h"), 10.0, Mm(70.0), Mm(y), font);
+                layer.use_text(
+                    format!("{regular:.2} regular"),
+                    10.0,
+                    Mm(100.0),
+                    Mm(y),
+                    font,
+                );
+                layer.use_text(
+                    format!("{overtime:.2} overtime"),
+                    10.0,
+                    Mm(140.0),
+                    Mm(y),
+                    font,
+                );
+                y -= LINE;
+            }
+            y -= LINE;
+        }
+        Ok(doc.save_to_bytes()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(period: &str, start: Option<&str>, end: Option<&str>) -> PayrollOpts {
+        PayrollOpts {
+            period: Some(period.to_string()),
+            start: start.map(|start| start.parse().unwrap()),
+            end: end.map(|end| end.parse().unwrap()),
+            location: None,
+            round_to: None,
+            daily_overtime: None,
+            weekly_overtime: None,
+            format: None,
+        }
+    }
+
+    fn session(location: i32, entry: &str, exit: &str) -> Session {
+        Session {
+            doc: 1,
+            location,
+            entry_id: 0,
+            entry: entry.parse().unwrap(),
+            exit_id: Some(0),
+            exit: Some(exit.parse().unwrap()),
+            flags: vec![],
+            adjusted: false,
+        }
+    }
+
+    fn report(opts: &PayrollOpts, sessions: &[Session]) -> PayrollReport {
+        let names = BTreeMap::from([(1, "Resident".to_string())]);
+        let locations = BTreeMap::from([(1, "Kitchen".to_string()), (2, "Laundry".to_string())]);
+        PayrollReport::new(
+            opts,
+            opts.pay_period().unwrap(),
+            sessions,
+            &names,
+            &locations,
+        )
+    }
+
+    #[test]
+    fn daily_overtime() {
+        let mut opts = opts("weekly", Some("2024-03-04"), None);
+        opts.daily_overtime = Some(8.0);
+        let report = report(
+            &opts,
+            &[session(1, "2024-03-04T06:00:00", "2024-03-04T16:00:00")],
+        );
+        assert_eq!(report.residents[0].regular_hours, 8.0);
+        assert_eq!(report.residents[0].overtime_hours, 2.0);
+    }
+
+    #[test]
+    fn weekly_overtime() {
+        let opts = opts("weekly", Some("2024-03-04"), None);
+        let sessions: Vec<Session> = (4..9)
+            .map(|day| {
+                session(
+                    1,
+                    &format!("2024-03-0{day}T08:00:00"),
+                    &format!("2024-03-0{day}T17:00:00"),
+                )
+            })
+            .collect();
+        let resident = &report(&opts, &sessions).residents[0];
+        assert_eq!(resident.regular_hours, 40.0);
+        assert_eq!(resident.overtime_hours, 5.0);
+        assert_eq!(resident.days[4].regular_hours, 4.0);
+        assert_eq!(resident.days[4].overtime_hours, 5.0);
+    }
+
+    #[test]
+    fn weekly_overtime_across_locations() {
+        let opts = opts("weekly", Some("2024-03-04"), None);
+        let sessions: Vec<Session> = (4..9)
+            .flat_map(|day| {
+                [
+                    session(
+                        1,
+                        &format!("2024-03-0{day}T06:00:00"),
+                        &format!("2024-03-0{day}T12:00:00"),
+                    ),
+                    session(
+                        2,
+                        &format!("2024-03-0{day}T12:00:00"),
+                        &format!("2024-03-0{day}T18:00:00"),
+                    ),
+                ]
+            })
+            .collect();
+        let report = report(&opts, &sessions);
+        assert_eq!(report.residents.len(), 2);
+        for resident in &report.residents {
+            assert_eq!(resident.total_hours, 30.0);
+            assert_eq!(resident.regular_hours, 20.0);
+            assert_eq!(resident.overtime_hours, 10.0);
+        }
+    }
+
+    #[test]
+    fn weeks_start_on_monday_in_custom_periods() {
+        // wednesday to the next tuesday
+        let mut opts = opts("custom", Some("2024-03-06"), Some("2024-03-12"));
+        opts.weekly_overtime = Some(16.0);
+        let sessions = [
+            session(1, "2024-03-06T08:00:00", "2024-03-06T16:00:00"),
+            session(1, "2024-03-07T08:00:00", "2024-03-07T16:00:00"),
+            session(1, "2024-03-08T08:00:00", "2024-03-08T16:00:00"),
+            session(1, "2024-03-11T08:00:00", "2024-03-11T16:00:00"),
+        ];
+        let resident = &report(&opts, &sessions).residents[0];
+        assert_eq!(resident.overtime_hours, 8.0);
+        assert_eq!(resident.days[3].regular_hours, 8.0);
+    }
+
+    #[test]
+    fn pay_period_bounds() {
+        let biweekly = opts("biweekly", Some("2024-03-04"), None)
+            .pay_period()
+            .unwrap();
+        assert_eq!(biweekly.end, "2024-03-17".parse::<NaiveDate>().unwrap());
+        let custom = opts("custom", Some("2024-03-06"), Some("2024-03-12"))
+            .pay_period()
+            .unwrap();
+        assert_eq!(
+            custom.first_moment(),
+            "2024-03-06T00:00:00".parse().unwrap()
+        );
+        assert_eq!(custom.last_moment(), "2024-03-13T00:00:00".parse().unwrap());
+        assert!(opts("custom", Some("2024-03-06"), None)
+            .pay_period()
+            .is_err());
+        assert!(opts("custom", Some("2024-03-06"), Some("2024-03-05"))
+            .pay_period()
+            .is_err());
+        assert!(opts("monthly", None, None).pay_period().is_err());
+        let weekly = opts("weekly", None, None).pay_period().unwrap();
+        assert_eq!(weekly.start.weekday(), chrono::Weekday::Mon);
+    }
+
+    #[test]
+    fn rounds_to_the_nearest_increment() {
+        let mut opts = opts("weekly", None, None);
+        opts.round_to = Some(15);
+        let round = |ts: &str| opts.round(ts.parse().unwrap()).to_string();
+        assert_eq!(round("2024-03-04T08:07:00"), "2024-03-04 08:00:00");
+        assert_eq!(round("2024-03-04T08:08:00"), "2024-03-04 08:15:00");
+        assert_eq!(round("2024-03-04T23:53:00"), "2024-03-05 00:00:00");
+    }
+}
@@ -116,6 +116,10 @@ pub struct ResidentHours {
     pub resident_doc: i32,
     pub hours: f32,
     pub location: i32,
+    /// open, overnight, overlong or double scanned sessions counted in these hours
+    pub flagged_sessions: usize,
+    /// sessions with a scan corrected by a supervisor
+    pub adjusted_sessions: usize,
 }
 
 impl<T> Response<T>
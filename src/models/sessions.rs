@@ -0,0 +1,281 @@
+use chrono::{Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use entity::prelude::OrmSerializable;
+use entity::{timestamp_adjustments, timestamps};
+use sea_orm::{ColumnTrait, DatabaseConnection, DbErr, EntityTrait, QueryFilter, QueryOrder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const DEFAULT_MAX_HOURS: f32 = 12.0;
+const DEFAULT_DUPLICATE_SECONDS: i64 = 60;
+
+#[derive(Debug, Deserialize)]
+pub struct SessionOpts {
+    // query string parameters
+    pub doc: Option<i32>,
+    pub location: Option<i32>,
+    /// first day to reconstruct, defaults to a week ago
+    pub start: Option<NaiveDate>,
+    /// last day to reconstruct, defaults to today
+    pub end: Option<NaiveDate>,
+    /// sessions longer than this many hours are flagged, defaults to 12
+    pub max_hours: Option<f32>,
+    /// a scan this many seconds after the previous one is taken as a double tap, defaults to 60
+    pub duplicate_seconds: Option<i64>,
+    /// only return sessions that need review
+    pub flagged: Option<bool>,
+}
+
+impl SessionOpts {
+    pub fn limits(&self) -> SessionLimits {
+        SessionLimits {
+            max_hours: self.max_hours.unwrap_or(DEFAULT_MAX_HOURS),
+            duplicate_seconds: self.duplicate_seconds.unwrap_or(DEFAULT_DUPLICATE_SECONDS),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct SessionLimits {
+    pub max_hours: f32,
+    pub duplicate_seconds: i64,
+}
+
+impl Default for SessionLimits {
+    fn default() -> Self {
+        Self {
+            max_hours: DEFAULT_MAX_HOURS,
+            duplicate_seconds: DEFAULT_DUPLICATE_SECONDS,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionFlag {
+    /// the resident has not scanned anywhere since
+    Open,
+    SpansMidnight,
+    OverLimit,
+    /// ended by a second scan right after the first, or by a scan at the same location
+    DuplicateScan,
+}
+
+/// Time a resident spent at one location, from the scan that took them there until their next scan
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub doc: i32,
+    pub location: i32,
+    pub entry_id: i32,
+    pub entry: NaiveDateTime,
+    pub exit_id: Option<i32>,
+    pub exit: Option<NaiveDateTime>,
+    pub flags: Vec<SessionFlag>,
+    /// a scan in this session was inserted, edited or voided by hand
+    pub adjusted: bool,
+}
+
+impl OrmSerializable for Session {}
+
+impl Session {
+    pub fn seconds(&self) -> i64 {
+        self.exit.map_or(0, |exit| {
+            exit.signed_duration_since(self.entry).num_seconds()
+        })
+    }
+
+    pub fn is_flagged(&self) -> bool {
+        !self.flags.is_empty()
+    }
+
+    fn close(&mut self, exit: &timestamps::Model, limits: &SessionLimits) {
+        self.exit_id = Some(exit.id);
+        self.exit = Some(exit.ts);
+        let seconds = self.seconds();
+        if seconds <= limits.duplicate_seconds || exit.location == self.location {
+            self.flags.push(SessionFlag::DuplicateScan);
+        }
+        if self.entry.date() != exit.ts.date() && exit.ts != exit.ts.date().and_time(NaiveTime::MIN)
+        {
+            self.flags.push(SessionFlag::SpansMidnight);
+        }
+        if seconds > (limits.max_hours * 3600.0) as i64 {
+            self.flags.push(SessionFlag::OverLimit);
+        }
+    }
+}
+
+/// Pairs every scan with the same resident's next scan. The timestamps must be ordered by `ts`,
+/// a sign out (location 0) only ends the session before it.
+pub fn reconstruct(timestamps: &[timestamps::Model], limits: &SessionLimits) -> Vec<Session> {
+    let mut open: HashMap<i32, Session> = HashMap::new();
+    let mut sessions = Vec::new();
+    for timestamp in timestamps {
+        if let Some(mut session) = open.remove(&timestamp.doc) {
+            session.close(timestamp, limits);
+            sessions.push(session);
+        }
+        if timestamp.location != 0 {
+            open.insert(
+                timestamp.doc,
+                Session {
+                    doc: timestamp.doc,
+                    location: timestamp.location,
+                    entry_id: timestamp.id,
+                    entry: timestamp.ts,
+                    exit_id: None,
+                    exit: None,
+                    flags: vec![],
+                    adjusted: false,
+                },
+            );
+        }
+    }
+    sessions.extend(open.into_values().map(|mut session| {
+        session.flags.push(SessionFlag::Open);
+        session
+    }));
+    sessions.sort_by_key(|session| (session.doc, session.entry));
+    sessions
+}
+
+/// Range of timestamps to load for sessions starting between the two days, reaching one day
+/// past either end to find the scans that open or close a session crossing them
+pub fn lookup_range(start: NaiveDate, end: NaiveDate) -> (NaiveDateTime, NaiveDateTime) {
+    (
+        start.and_time(NaiveTime::MIN) - Duration::days(1),
+        end.and_time(NaiveTime::MIN) + Duration::days(2),
+    )
+}
+
+/// Reconstructs the sessions from the timestamps between the two moments, optionally for one resident.
+/// Voided timestamps are skipped, a session is marked adjusted if one of its scans was corrected or a
+/// voided scan used to fall inside it.
+pub async fn load(
+    db: &DatabaseConnection,
+    (from, until): (NaiveDateTime, NaiveDateTime),
+    doc: Option<i32>,
+    limits: &SessionLimits,
+) -> Result<Vec<Session>, DbErr> {
+    let mut query = timestamps::Entity::find().filter(timestamps::Column::Ts.between(from, until));
+    if let Some(doc) = doc {
+        query = query.filter(timestamps::Column::Doc.eq(doc));
+    }
+    let (voided, timestamps): (Vec<_>, Vec<_>) = query
+        .order_by_asc(timestamps::Column::Ts)
+        .all(db)
+        .await?
+        .into_iter()
+        .partition(|timestamp| timestamp.is_voided);
+    let adjusted = timestamp_adjustments::Model::adjusted_ids(
+        db,
+        timestamps.iter().map(|timestamp| timestamp.id).collect(),
+    )
+    .await?;
+    let mut sessions = reconstruct(&timestamps, limits);
+    for session in &mut sessions {
+        session.adjusted = adjusted.contains(&session.entry_id)
+            || session.exit_id.is_some_and(|id| adjusted.contains(&id))
+            || voided.iter().any(|void| {
+                void.doc == session.doc
+                    && void.ts >= session.entry
+                    && session.exit.is_none_or(|exit| void.ts <= exit)
+            });
+    }
+    Ok(sessions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scan(id: i32, location: i32, ts: &str) -> timestamps::Model {
+        timestamps::Model {
+            id,
+            doc: 1,
+            location,
+            ts: ts.parse().unwrap(),
+            device_id: None,
+            idempotency_key: None,
+            is_voided: false,
+        }
+    }
+
+    #[test]
+    fn pairs_scans_without_flags() {
+        let sessions = reconstruct(
+            &[
+                scan(1, 4, "2024-03-04T08:00:00"),
+                scan(2, 5, "2024-03-04T12:00:00"),
+                scan(3, 0, "2024-03-04T16:00:00"),
+            ],
+            &SessionLimits::default(),
+        );
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].location, 4);
+        assert_eq!(sessions[0].seconds(), 4 * 3600);
+        assert_eq!(sessions[1].exit_id, Some(3));
+        assert!(!sessions.iter().any(Session::is_flagged));
+    }
+
+    #[test]
+    fn flags_open_session() {
+        let sessions = reconstruct(
+            &[scan(1, 4, "2024-03-04T08:00:00")],
+            &SessionLimits::default(),
+        );
+        assert_eq!(sessions[0].flags, vec![SessionFlag::Open]);
+        assert_eq!(sessions[0].seconds(), 0);
+    }
+
+    #[test]
+    fn flags_midnight_split() {
+        let sessions = reconstruct(
+            &[
+                scan(1, 4, "2024-03-04T22:00:00"),
+                scan(2, 0, "2024-03-05T02:00:00"),
+            ],
+            &SessionLimits::default(),
+        );
+        assert_eq!(sessions[0].flags, vec![SessionFlag::SpansMidnight]);
+        // ending exactly at midnight stays on the one day
+        let sessions = reconstruct(
+            &[
+                scan(1, 4, "2024-03-04T22:00:00"),
+                scan(2, 0, "2024-03-05T00:00:00"),
+            ],
+            &SessionLimits::default(),
+        );
+        assert!(sessions[0].flags.is_empty());
+    }
+
+    #[test]
+    fn flags_over_limit() {
+        let limits = SessionLimits {
+            max_hours: 8.0,
+            ..SessionLimits::default()
+        };
+        let sessions = reconstruct(
+            &[
+                scan(1, 4, "2024-03-04T06:00:00"),
+                scan(2, 0, "2024-03-04T15:00:00"),
+            ],
+            &limits,
+        );
+        assert_eq!(sessions[0].flags, vec![SessionFlag::OverLimit]);
+    }
+
+    #[test]
+    fn flags_duplicate_scans() {
+        let sessions = reconstruct(
+            &[
+                scan(1, 4, "2024-03-04T08:00:00"),
+                scan(2, 5, "2024-03-04T08:00:30"),
+                // the same location again, however long after
+                scan(3, 5, "2024-03-04T12:00:00"),
+            ],
+            &SessionLimits::default(),
+        );
+        assert_eq!(sessions[0].flags, vec![SessionFlag::DuplicateScan]);
+        assert_eq!(sessions[1].flags, vec![SessionFlag::DuplicateScan]);
+    }
+}
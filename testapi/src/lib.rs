@@ -5,15 +5,14 @@ pub mod testapi {
     use serde_json::{json, Value};
     use std::{collections::HashMap, time::Duration};
 
+    // every route but logging in needs a session, so requests are made logged in as the admin
     fn make_request(
         endpoint: &str,
         method: reqwest::Method,
         body: Option<HashMap<&str, &str>>,
     ) -> Response {
-        let ip = std::env::var("LOCAL_IP").unwrap_or("127.0.0.1".to_string());
-        let ip = format!("{ip}:8080");
-        let client = reqwest::blocking::Client::new();
-        let url = format!("http://{ip}/api/{endpoint}");
+        let (client, ip) = login();
+        let url = format!("{ip}/{endpoint}");
         let request_builder = match method {
             reqwest::Method::GET => client.get(&url),
             reqwest::Method::POST => client.post(&url).json(&body.unwrap()),
@@ -46,10 +45,9 @@ pub mod testapi {
     }
     #[test]
     fn test_residents_create() {
-        let ip = std::env::var("LOCAL_IP").unwrap_or("localhost".to_string());
-        let ip = format!("http://{}:8080/api", ip);
+        let (client, ip) = login();
         let fake_location = json!({"rfid": "338888222889999", "name": "Fake resident", "doc": 29752, "room": "C-8", "unit": 4, "current_location": 4, "level": 4});
-        let resp = reqwest::blocking::Client::new()
+        let resp = client
             .post(format!("{}/residents", ip))
             .json(&fake_location)
             .timeout(Duration::from_millis(20))
@@ -98,11 +96,9 @@ pub mod testapi {
     }
     #[test]
     fn test_locations_create() {
-        let ip: &str =
-            &std::env::var("LOCAL_IP").unwrap_or("http://localhost:8080/api".to_string());
-        let ip = format!("http://{}:8080/api", ip);
+        let (client, ip) = login();
         let fake_location = json!({"id": 69, "name": "Fake Location", "level": 2});
-        let resp = reqwest::blocking::Client::new()
+        let resp = client
             .post(format!("{}/locations", ip))
             .json(&fake_location)
             .timeout(Duration::from_millis(20))
@@ -111,6 +107,39 @@ pub mod testapi {
         assert_eq!(resp.status().as_u16(), 200);
     }
 
+    #[test]
+    fn test_locations_rename_keeps_settings() {
+        let (client, ip) = login();
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let id = 1000 + (nanos % 1_000_000) as i32;
+        let location = json!({"id": id, "name": "Workshop", "level": 1, "is_work": true, "capacity": 5, "rejects_over_limit": true});
+        client
+            .post(format!("{}/locations", ip))
+            .json(&location)
+            .send()
+            .unwrap();
+        let rename = json!({"name": "Renamed Workshop"});
+        let resp = client
+            .patch(format!("{}/locations/{}", ip, id))
+            .json(&rename)
+            .send()
+            .unwrap();
+        assert_eq!(resp.json::<Value>().unwrap()["success"], true);
+        let location = client
+            .get(format!("{}/locations/{}", ip, id))
+            .send()
+            .unwrap()
+            .json::<Value>()
+            .unwrap();
+        let location = &location["data"][0];
+        assert_eq!(location["name"], "Renamed Workshop");
+        assert_eq!(location["is_work"], true);
+        assert_eq!(location["capacity"], 5);
+        assert_eq!(location["rejects_over_limit"], true);
+    }
     #[test]
     fn test_locations_timestamps() {
         let response = make_request("locations/8/timestamps", reqwest::Method::GET, None);
@@ -186,4 +215,809 @@ pub mod testapi {
         );
         assert_eq!(response.status().as_u16(), 200);
     }
+
+    #[test]
+    fn test_users_requires_admin_level() {
+        let ip = std::env::var("LOCAL_IP").unwrap_or("localhost".to_string());
+        let response = reqwest::blocking::Client::new()
+            .get(format!("http://{}:8080/api/users", ip))
+            .send()
+            .expect("Failed to execute request");
+        assert_eq!(response.status().as_u16(), 401);
+
+        let (client, ip) = login();
+        let (email, _) = create_user(&client, &ip, 1);
+        let officer = reqwest::blocking::Client::builder()
+            .cookie_store(true)
+            .build()
+            .unwrap();
+        let resp = officer
+            .post(format!("{}/auth/login", ip))
+            .json(&json!({"email": email, "password": "password1"}))
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 200);
+        let resp = officer.get(format!("{}/users", ip)).send().unwrap();
+        assert_eq!(resp.status().as_u16(), 403);
+    }
+
+    #[test]
+    fn test_device_scan_requires_key() {
+        let mut body = HashMap::new();
+        body.insert("rfid", "111111111111111");
+        let response = make_request("devices/scan", reqwest::Method::POST, Some(body));
+        assert_eq!(response.status().as_u16(), 401);
+    }
+
+    #[test]
+    fn test_timestamps_batch_deduplicates() {
+        let (client, ip) = login();
+        let key = format!("testapi-{:?}", std::time::SystemTime::now());
+        let batch = json!([{"rfid": "123455623562354", "location": 9, "ts": "2024-03-01T08:00:00", "idempotency_key": key}]);
+        let mut statuses = vec![];
+        for _ in 0..2 {
+            let resp = client
+                .post(format!("{}/timestamps/batch", ip))
+                .json(&batch)
+                .timeout(Duration::from_millis(500))
+                .send()
+                .expect("Failed to execute request");
+            assert_eq!(resp.status().as_u16(), 200);
+            statuses.push(resp.json::<Value>().unwrap()["data"][0]["status"].clone());
+        }
+        assert_eq!(statuses, vec!["recorded", "duplicate"]);
+    }
+
+    #[test]
+    fn test_timestamps_batch_rejection_recorded_once() {
+        let (client, ip) = login();
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let location = 1000 + (nanos % 1_000_000) as i32;
+        let restricted = json!({"id": location, "name": "Restricted Location", "level": 1});
+        let resp = client
+            .post(format!("{}/locations", ip))
+            .json(&restricted)
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 200);
+        let restriction = json!({"location": location, "reason": "testapi"});
+        let resp = client
+            .post(format!("{}/residents/1233495/restrictions", ip))
+            .json(&restriction)
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 201);
+        let restriction_id = resp.json::<Value>().unwrap()["data"][0]["id"]
+            .as_i64()
+            .unwrap();
+        let batch = json!([{"rfid": "123455623562354", "location": location, "ts": "2024-03-01T08:00:00", "idempotency_key": format!("testapi-{}", nanos)}]);
+        for _ in 0..2 {
+            let resp = client
+                .post(format!("{}/timestamps/batch", ip))
+                .json(&batch)
+                .send()
+                .unwrap();
+            assert_eq!(
+                resp.json::<Value>().unwrap()["data"][0]["status"],
+                "rejected"
+            );
+        }
+        let rejected = client
+            .get(format!("{}/rejected_scans?location={}", ip, location))
+            .send()
+            .unwrap()
+            .json::<Value>()
+            .unwrap();
+        assert_eq!(rejected["data"].as_array().unwrap().len(), 1);
+        client
+            .delete(format!("{}/restrictions/{}", ip, restriction_id))
+            .send()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_correction_unknown_location_refused() {
+        let (client, ip) = login();
+        let correction = json!({"doc": 1233495, "location": 987654, "ts": "2024-03-01T08:00:00", "reason": "testapi"});
+        let resp = client
+            .post(format!("{}/timestamps/corrections", ip))
+            .json(&correction)
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 400);
+        assert_eq!(
+            resp.json::<Value>().unwrap()["message"],
+            "Location not found"
+        );
+    }
+
+    #[test]
+    fn test_unauthenticated_requests_are_refused() {
+        let ip = std::env::var("LOCAL_IP").unwrap_or("localhost".to_string());
+        let ip = format!("http://{}:8080/api", ip);
+        let client = reqwest::blocking::Client::new();
+        let routes = [
+            (reqwest::Method::GET, "locations"),
+            (reqwest::Method::POST, "locations"),
+            (reqwest::Method::PATCH, "locations/4"),
+            (reqwest::Method::DELETE, "locations/4"),
+            (reqwest::Method::GET, "residents"),
+            (reqwest::Method::POST, "residents"),
+            (reqwest::Method::PATCH, "residents/111111111111111"),
+            (reqwest::Method::DELETE, "residents/111111111111111"),
+            (reqwest::Method::POST, "residents/1233495/upload"),
+            (reqwest::Method::GET, "timestamps"),
+            (reqwest::Method::POST, "timestamps"),
+            (reqwest::Method::POST, "timestamps/batch"),
+            (reqwest::Method::POST, "items"),
+            (reqwest::Method::PATCH, "items/1"),
+            (reqwest::Method::GET, "accounts"),
+            (reqwest::Method::POST, "accounts/1/transactions"),
+        ];
+        for (method, endpoint) in routes {
+            let resp = client
+                .request(method.clone(), format!("{}/{}", ip, endpoint))
+                .json(&json!({}))
+                .send()
+                .expect("Failed to execute request");
+            assert_eq!(resp.status().as_u16(), 401, "{} {}", method, endpoint);
+            assert_eq!(resp.json::<Value>().unwrap()["success"], false);
+        }
+    }
+
+    #[test]
+    fn test_bearer_tokens_refresh_and_revoke() {
+        let ip = std::env::var("LOCAL_IP").unwrap_or("localhost".to_string());
+        let ip = format!("http://{}:8080/api", ip);
+        let client = reqwest::blocking::Client::new();
+        let tokens = |resp: reqwest::blocking::Response| {
+            let body = resp.json::<Value>().unwrap();
+            let pair = &body["data"][0];
+            (
+                pair["access_token"].as_str().unwrap().to_string(),
+                pair["refresh_token"].as_str().unwrap().to_string(),
+            )
+        };
+        let resp = client
+            .post(format!("{}/auth/login", ip))
+            .json(&json!({"email": "admin", "password": "admin"}))
+            .send()
+            .unwrap();
+        let (access, refresh) = tokens(resp);
+        let resp = client
+            .get(format!("{}/users", ip))
+            .bearer_auth(&access)
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 200);
+        // a refresh token isn't an access token
+        let resp = client
+            .get(format!("{}/users", ip))
+            .bearer_auth(&refresh)
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 401);
+
+        let resp = client
+            .post(format!("{}/auth/refresh", ip))
+            .json(&json!({ "refresh_token": refresh }))
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 200);
+        let (access, _) = tokens(resp);
+        let resp = client
+            .get(format!("{}/users", ip))
+            .bearer_auth(&access)
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 200);
+
+        // replaying the rotated refresh token revokes the whole login
+        let resp = client
+            .post(format!("{}/auth/refresh", ip))
+            .json(&json!({ "refresh_token": refresh }))
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 401);
+        let resp = client
+            .get(format!("{}/users", ip))
+            .bearer_auth(&access)
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 401);
+    }
+
+    /// A client connecting from a loopback address of its own, so its failed logins only throttle itself
+    fn isolated_client(nanos: u128) -> reqwest::blocking::Client {
+        let source = std::net::Ipv4Addr::new(
+            127,
+            (nanos % 250) as u8 + 1,
+            ((nanos / 250) % 250) as u8,
+            ((nanos / 62500) % 250) as u8 + 2,
+        );
+        reqwest::blocking::Client::builder()
+            .local_address(std::net::IpAddr::V4(source))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_failed_logins_lock_out() {
+        let ip = std::env::var("LOCAL_IP").unwrap_or("localhost".to_string());
+        let ip = format!("http://{}:8080/api", ip);
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let email = format!("nobody{}@example.com", nanos);
+        let client = isolated_client(nanos);
+        let attempt = || {
+            client
+                .post(format!("{}/auth/login", ip))
+                .json(&json!({"email": email, "password": "wrong"}))
+                .send()
+                .unwrap()
+        };
+        for _ in 0..5 {
+            let resp = attempt();
+            assert_eq!(resp.status().as_u16(), 401);
+            // the same answer whether or not the email has an account
+            assert_eq!(
+                resp.json::<Value>().unwrap()["message"],
+                "Invalid email or password"
+            );
+        }
+        assert_eq!(attempt().status().as_u16(), 429);
+
+        let (client, ip) = login();
+        let resp = client
+            .get(format!(
+                "{}/auth/events?email={}&kind=failed_login",
+                ip, email
+            ))
+            .send()
+            .unwrap();
+        assert_eq!(
+            resp.json::<Value>().unwrap()["data"]
+                .as_array()
+                .unwrap()
+                .len(),
+            5
+        );
+    }
+
+    #[test]
+    fn test_forwarded_for_does_not_escape_throttle() {
+        let ip = std::env::var("LOCAL_IP").unwrap_or("localhost".to_string());
+        let ip = format!("http://{}:8080/api", ip);
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+            + 7;
+        let client = isolated_client(nanos);
+        // a new email and a new claimed ip every time, only the peer address stays the same
+        let attempt = |n: u128, password: &str, email: String| {
+            client
+                .post(format!("{}/auth/login", ip))
+                .header("X-Forwarded-For", format!("10.0.{}.{}", n / 250, n % 250))
+                .json(&json!({"email": email, "password": password}))
+                .send()
+                .unwrap()
+        };
+        for n in 0..20 {
+            let resp = attempt(n, "wrong", format!("spoof{}-{}@example.com", nanos, n));
+            assert_eq!(resp.status().as_u16(), 401);
+        }
+        let resp = attempt(20, "admin", "admin".to_string());
+        assert_eq!(resp.status().as_u16(), 429);
+    }
+
+    #[test]
+    fn test_users_weak_password_refused() {
+        let (client, ip) = login();
+        for password in ["short1", "onlyletters", "1234567890"] {
+            let resp = client
+                .post(format!("{}/users", ip))
+                .json(&json!({"email": "weak@example.com", "password": password}))
+                .send()
+                .unwrap();
+            assert_eq!(resp.status().as_u16(), 400, "{}", password);
+        }
+    }
+
+    // a new user at the level, returning their email and id
+    fn create_user(client: &reqwest::blocking::Client, ip: &str, level: i32) -> (String, i64) {
+        let email = format!(
+            "user{}@example.com",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        );
+        let user = json!({"email": email, "password": "password1", "level": level});
+        let resp = client
+            .post(format!("{}/users", ip))
+            .json(&user)
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 200);
+        let users = client
+            .get(format!("{}/users", ip))
+            .send()
+            .unwrap()
+            .json::<Value>()
+            .unwrap();
+        let id = users["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|user| user["email"] == email.as_str())
+            .unwrap()["id"]
+            .as_i64()
+            .unwrap();
+        (email, id)
+    }
+
+    #[test]
+    fn test_users_with_history_are_not_deleted() {
+        let (client, ip) = login();
+        let (_, unused) = create_user(&client, &ip, 1);
+        let resp = client
+            .delete(format!("{}/users/{}", ip, unused))
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 200);
+        let (email, used) = create_user(&client, &ip, 1);
+        let resp = reqwest::blocking::Client::new()
+            .post(format!("{}/auth/login", ip))
+            .json(&json!({"email": email, "password": "password1"}))
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 200);
+        let resp = client
+            .delete(format!("{}/users/{}", ip, used))
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 409);
+    }
+
+    fn login() -> (reqwest::blocking::Client, String) {
+        let ip = std::env::var("LOCAL_IP").unwrap_or("localhost".to_string());
+        let ip = format!("http://{}:8080/api", ip);
+        let client = reqwest::blocking::Client::builder()
+            .cookie_store(true)
+            .build()
+            .unwrap();
+        let resp = client
+            .post(format!("{}/auth/login", ip))
+            .json(&json!({"email": "admin", "password": "admin"}))
+            .send()
+            .expect("Failed to execute request");
+        assert_eq!(resp.status().as_u16(), 200);
+        (client, ip)
+    }
+
+    #[test]
+    fn test_concurrent_purchases_never_overdraw() {
+        let (client, ip) = login();
+        let upc = format!(
+            "{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        );
+        let item = json!({"upc": upc, "name": "Concurrency test", "price": 5.0, "quantity": 1000});
+        let resp = client
+            .post(format!("{}/items", ip))
+            .json(&item)
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 201);
+        let credit = json!({"doc": 1233495, "kind": "credit", "amount": 20.0});
+        let resp = client
+            .post(format!("{}/accounts/1/transactions", ip))
+            .json(&credit)
+            .send()
+            .unwrap();
+        let balance = resp.json::<Value>().unwrap()["data"][0]["balance"]
+            .as_i64()
+            .unwrap();
+        // each purchase costs more than a third of the balance, so at most two can go through
+        let quantity = balance / (500 * 3) + 1;
+        let cost = quantity * 500;
+        let purchase = json!({"doc": 1233495, "kind": "purchase", "amount": 0.0, "items": [{"upc": upc, "quantity": quantity}]});
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let (client, url, purchase) = (
+                    client.clone(),
+                    format!("{}/accounts/1/transactions", ip),
+                    purchase.clone(),
+                );
+                std::thread::spawn(move || {
+                    client
+                        .post(url)
+                        .json(&purchase)
+                        .send()
+                        .unwrap()
+                        .json::<Value>()
+                        .unwrap()["success"]
+                        == true
+                })
+            })
+            .collect();
+        let succeeded = handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .filter(|ok| *ok)
+            .count() as i64;
+        assert_eq!(succeeded, balance / cost);
+        let resp = client.get(format!("{}/accounts/1", ip)).send().unwrap();
+        let remaining = resp.json::<Value>().unwrap()["data"][0]["balance"]
+            .as_i64()
+            .unwrap();
+        assert_eq!(remaining, balance - succeeded * cost);
+    }
+
+    #[test]
+    fn test_transaction_kind_must_be_known() {
+        let (client, ip) = login();
+        let balance = || {
+            client
+                .get(format!("{}/accounts/1", ip))
+                .send()
+                .unwrap()
+                .json::<Value>()
+                .unwrap()["data"][0]["balance"]
+                .as_i64()
+                .unwrap()
+        };
+        let before = balance();
+        // only the void and refund endpoints make reversals, anything else isn't a purchase either
+        for kind in ["refund", "void", "x"] {
+            let body = json!({"doc": 1233495, "kind": kind, "amount": 0.0, "items": [{"upc": "123456789012", "quantity": 1}]});
+            let resp = client
+                .post(format!("{}/accounts/1/transactions", ip))
+                .json(&body)
+                .send()
+                .unwrap();
+            assert_eq!(resp.json::<Value>().unwrap()["success"], false, "{}", kind);
+        }
+        assert_eq!(balance(), before);
+    }
+
+    #[test]
+    fn test_refund_restores_only_what_was_spent() {
+        let (client, ip) = login();
+        let upc = format!(
+            "{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        );
+        let item = json!({"upc": upc, "name": "Allowance test", "price": 3.0, "quantity": 100});
+        let resp = client
+            .post(format!("{}/items", ip))
+            .json(&item)
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 201);
+        let post = |body: Value| {
+            client
+                .post(format!("{}/accounts/2/transactions", ip))
+                .json(&body)
+                .send()
+                .unwrap()
+                .json::<Value>()
+                .unwrap()
+        };
+        post(json!({"doc": 6789190, "kind": "credit", "amount": 20.0}));
+        let weekly_spent = || {
+            client
+                .get(format!("{}/residents/6789190/allowance", ip))
+                .send()
+                .unwrap()
+                .json::<Value>()
+                .unwrap()["data"][0]["weekly_spent"]
+                .as_i64()
+                .unwrap()
+        };
+        let before = weekly_spent();
+        let resp = post(
+            json!({"doc": 6789190, "kind": "purchase", "amount": 0.0, "items": [{"upc": upc, "quantity": 2}]}),
+        );
+        assert_eq!(resp["success"], true);
+        assert_eq!(weekly_spent(), before + 600);
+        let history = client
+            .get(format!(
+                "{}/accounts/6789190/transactions?per_page=1000",
+                ip
+            ))
+            .send()
+            .unwrap()
+            .json::<Value>()
+            .unwrap();
+        let purchase_id = history["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|transaction| transaction["kind"] == "purchase")
+            .filter_map(|transaction| transaction["id"].as_i64())
+            .max()
+            .unwrap();
+        let refund = || {
+            client
+                .post(format!("{}/transactions/{}/refund", ip, purchase_id))
+                .json(&json!({"items": [{"upc": upc, "quantity": 1}]}))
+                .send()
+                .unwrap()
+                .json::<Value>()
+                .unwrap()
+        };
+        assert_eq!(refund()["success"], true);
+        assert_eq!(weekly_spent(), before + 300);
+        assert_eq!(refund()["success"], true);
+        assert_eq!(weekly_spent(), before);
+        // nothing is left to give back, so the allowance can't rise past where it started
+        assert_eq!(refund()["success"], false);
+        assert_eq!(weekly_spent(), before);
+    }
+
+    #[test]
+    fn test_stock_count_keeps_changes_since_counting() {
+        let (client, ip) = login();
+        let upc = format!(
+            "{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        );
+        let item = json!({"upc": upc, "name": "Stock count test", "price": 1.0, "quantity": 10});
+        client
+            .post(format!("{}/items", ip))
+            .json(&item)
+            .send()
+            .unwrap();
+        let count = client
+            .post(format!("{}/stock_counts", ip))
+            .json(&json!({"note": "testapi"}))
+            .send()
+            .unwrap()
+            .json::<Value>()
+            .unwrap();
+        let count_id = count["data"][0]["stock_count"]["id"].as_i64().unwrap();
+        let entry = client
+            .post(format!("{}/stock_counts/{}/count", ip, count_id))
+            .json(&json!({"upc": upc, "quantity": 8}))
+            .send()
+            .unwrap()
+            .json::<Value>()
+            .unwrap();
+        let item_id = entry["data"][0]["item_id"].as_i64().unwrap();
+        // one more goes missing after the shelf was counted
+        client
+            .patch(format!("{}/items/{}", ip, item_id))
+            .json(&json!({"quantity": -1, "reason": "damaged"}))
+            .send()
+            .unwrap();
+        let report = client
+            .post(format!("{}/stock_counts/{}/post", ip, count_id))
+            .json(&json!({}))
+            .send()
+            .unwrap()
+            .json::<Value>()
+            .unwrap();
+        assert_eq!(report["data"][0]["lines"][0]["variance"], -2);
+        let items = client
+            .get(format!("{}/items?per_page=100000", ip))
+            .send()
+            .unwrap()
+            .json::<Value>()
+            .unwrap();
+        let item = items["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|item| item["upc"] == upc.as_str())
+            .unwrap()
+            .clone();
+        assert_eq!(item["quantity"], 7);
+    }
+
+    #[test]
+    fn test_headcount_outcomes() {
+        let (client, ip) = login();
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let unit = 1000 + (nanos % 1_000_000) as i32;
+        let location = json!({"id": unit, "name": "Headcount Unit", "level": 1});
+        client
+            .post(format!("{}/locations", ip))
+            .json(&location)
+            .send()
+            .unwrap();
+        // counted, missing, out at location 4 and signed out
+        let docs: Vec<i32> = (0..4)
+            .map(|i| 100_000_000 + (nanos % 10_000_000) as i32 * 4 + i)
+            .collect();
+        for (doc, current_location) in docs.iter().zip([unit, unit, 4, 0]) {
+            let resident = json!({"rfid": format!("9{}", doc), "name": "Headcount resident", "doc": doc, "room": "A-1", "unit": unit, "current_location": current_location, "level": 1});
+            let resp = client
+                .post(format!("{}/residents", ip))
+                .json(&resident)
+                .send()
+                .unwrap();
+            assert_eq!(resp.status().as_u16(), 201);
+        }
+        let headcount = client
+            .post(format!("{}/headcounts", ip))
+            .json(&json!({"location": unit}))
+            .send()
+            .unwrap()
+            .json::<Value>()
+            .unwrap();
+        let id = headcount["data"][0]["headcount"]["id"].as_i64().unwrap();
+        client
+            .post(format!("{}/headcounts/{}/scan", ip, id))
+            .json(&json!({"rfid": format!("9{}", docs[0])}))
+            .send()
+            .unwrap();
+        let report = client
+            .get(format!("{}/headcounts/{}", ip, id))
+            .send()
+            .unwrap()
+            .json::<Value>()
+            .unwrap();
+        let report = &report["data"][0];
+        assert_eq!(report["expected"], 2);
+        assert_eq!(report["confirmed"], 1);
+        assert_eq!(report["missing"][0]["doc"], docs[1]);
+        assert_eq!(report["out"][0]["doc"], docs[2]);
+        assert_eq!(report["out"][0]["location"], 4);
+        assert_eq!(report["signed_out"][0]["doc"], docs[3]);
+    }
+
+    #[test]
+    fn test_purchase_order_closed_short() {
+        let (client, ip) = login();
+        let upc = format!(
+            "{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        );
+        let item = json!({"upc": upc, "name": "Purchase order test", "price": 1.0, "quantity": 0});
+        client
+            .post(format!("{}/items", ip))
+            .json(&item)
+            .send()
+            .unwrap();
+        let items = client
+            .get(format!("{}/items?per_page=100000", ip))
+            .send()
+            .unwrap()
+            .json::<Value>()
+            .unwrap();
+        let item_id = items["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|item| item["upc"] == upc.as_str())
+            .unwrap()["id"]
+            .clone();
+        let missing = json!({"vendor": "testapi", "lines": [{"item_id": 999_999_999, "quantity": 1, "unit_cost": 0.5}]});
+        let resp = client
+            .post(format!("{}/purchase_orders", ip))
+            .json(&missing)
+            .send()
+            .unwrap();
+        assert_eq!(resp.status().as_u16(), 400);
+        let order = json!({"vendor": "testapi", "lines": [{"item_id": item_id, "quantity": 10, "unit_cost": 0.5}]});
+        let order = client
+            .post(format!("{}/purchase_orders", ip))
+            .json(&order)
+            .send()
+            .unwrap()
+            .json::<Value>()
+            .unwrap();
+        let id = order["data"][0]["order"]["id"].as_i64().unwrap();
+        let line_id = order["data"][0]["lines"][0]["id"].clone();
+        client
+            .post(format!("{}/purchase_orders/{}/order", ip, id))
+            .send()
+            .unwrap();
+        let receipt = json!({"lines": [{"line_id": line_id, "quantity": 6}]});
+        let detail = client
+            .post(format!("{}/purchase_orders/{}/receive", ip, id))
+            .json(&receipt)
+            .send()
+            .unwrap()
+            .json::<Value>()
+            .unwrap();
+        assert_eq!(detail["data"][0]["order"]["status"], "partially_received");
+        assert_eq!(detail["data"][0]["has_discrepancies"], false);
+        let detail = client
+            .post(format!("{}/purchase_orders/{}/close", ip, id))
+            .send()
+            .unwrap()
+            .json::<Value>()
+            .unwrap();
+        assert_eq!(detail["data"][0]["order"]["status"], "received");
+        assert_eq!(detail["data"][0]["has_discrepancies"], true);
+        assert_eq!(detail["data"][0]["lines"][0]["discrepancy"], -4);
+    }
+
+    #[test]
+    fn test_callouts_follow_corrections() {
+        let (client, ip) = login();
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let location = 1000 + (nanos % 1_000_000) as i32;
+        let doc = 200_000_000 + (nanos % 10_000_000) as i32;
+        client
+            .post(format!("{}/locations", ip))
+            .json(&json!({"id": location, "name": "Chapel", "level": 1}))
+            .send()
+            .unwrap();
+        let resident = json!({"rfid": format!("8{}", doc), "name": "Callout resident", "doc": doc, "room": "A-1", "unit": location, "current_location": 0, "level": 1});
+        client
+            .post(format!("{}/residents", ip))
+            .json(&resident)
+            .send()
+            .unwrap();
+        for day in ["2024-05-01", "2024-05-02"] {
+            let callout = json!({"doc": doc, "location": location, "arrive_from": format!("{day}T08:30:00"), "arrive_by": format!("{day}T09:00:00"), "return_by": format!("{day}T12:00:00")});
+            let resp = client
+                .post(format!("{}/callouts", ip))
+                .json(&callout)
+                .send()
+                .unwrap();
+            assert_eq!(resp.status().as_u16(), 201);
+        }
+        let correct = |location: i32, ts: &str| {
+            client
+                .post(format!("{}/timestamps/corrections", ip))
+                .json(&json!({"doc": doc, "location": location, "ts": ts, "reason": "testapi"}))
+                .send()
+                .unwrap()
+                .json::<Value>()
+                .unwrap()["data"][0]["id"]
+                .as_i64()
+                .unwrap()
+        };
+        let callouts = || {
+            client
+                .get(format!("{}/callouts?doc={}", ip, doc))
+                .send()
+                .unwrap()
+                .json::<Value>()
+                .unwrap()["data"]
+                .clone()
+        };
+        let arrived = correct(location, "2024-05-01T08:50:00");
+        correct(0, "2024-05-01T11:00:00");
+        let matched = callouts();
+        assert_eq!(matched[0]["status"], "on_time");
+        assert_eq!(matched[0]["returned_at"], "2024-05-01T11:00:00");
+        assert_eq!(matched[1]["status"], "missed");
+        client
+            .post(format!("{}/timestamps/{}/void", ip, arrived))
+            .json(&json!({"reason": "testapi"}))
+            .send()
+            .unwrap();
+        let unmatched = callouts();
+        assert_eq!(unmatched[0]["status"], "missed");
+        assert_eq!(unmatched[0]["arrived_at"], Value::Null);
+    }
 }